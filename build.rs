@@ -3,10 +3,6 @@ use std::io;
 use std::path::PathBuf;
 
 use lazy_static::lazy_static;
-use tonic_build;
-
-#[cfg(any(feature = "seccomp", feature = "cap-ng"))]
-use bindgen;
 
 lazy_static! {
     static ref OUT_DIR: PathBuf = {
@@ -0,0 +1 @@
+/usr/bin/gcc $SOURCE_FILE -o $EXECUTABLE_FILE -O2 -fno-asm -Wall -lm -static -static-libgcc -std=c99
\ No newline at end of file
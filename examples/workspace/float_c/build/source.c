@@ -0,0 +1,7 @@
+#include <stdio.h>
+int main() {
+  double a, b;
+  scanf("%lf%lf", &a, &b);
+  printf("%.10f\n", a / b);
+  return 0;
+}
@@ -0,0 +1,19 @@
+#include <stdio.h>
+#include <string.h>
+int main() {
+  int low = 1, high = 1000000;
+  char reply[4];
+  while (low <= high) {
+    int mid = low + (high - low) / 2;
+    printf("%d\n", mid);
+    fflush(stdout);
+    if (scanf("%3s", reply) != 1 || strcmp(reply, "=") == 0) {
+      break;
+    } else if (strcmp(reply, "<") == 0) {
+      low = mid + 1;
+    } else {
+      high = mid - 1;
+    }
+  }
+  return 0;
+}
@@ -0,0 +1,28 @@
+#include <stdio.h>
+#define AC 0
+#define WA 1
+int main(int argc, char *args[]) {
+  FILE *f_in = fopen(args[1], "r");
+  FILE *f_ans = fopen(args[2], "r");
+  int secret, max_guesses;
+  fscanf(f_in, "%d", &secret);
+  fscanf(f_ans, "%d", &max_guesses);
+  for (int i = 0; i < max_guesses; i++) {
+    int guess;
+    if (scanf("%d", &guess) != 1) {
+      return WA;
+    }
+    if (guess < secret) {
+      printf("<\n");
+    } else if (guess > secret) {
+      printf(">\n");
+    } else {
+      printf("=\n");
+      fflush(stdout);
+      return AC;
+    }
+    fflush(stdout);
+  }
+  fprintf(stderr, "Too many guesses");
+  return WA;
+}
@@ -0,0 +1,7 @@
+#include <stdio.h>
+int main() {
+  int a, b;
+  scanf("%d%d", &a, &b);
+  printf("%d", a + b);
+  return 0;
+}
\ No newline at end of file
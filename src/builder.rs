@@ -7,66 +7,80 @@ use std::time::Duration;
 
 use crate::language::Language;
 use crate::process::ChildExt;
-use crate::workspace::BuildDir;
-use crate::process::cgroup;
-use crate::process::cgroup::CommandExt;
+use crate::workspace::{runtime::CgroupsConfig, BuildDir};
+use crate::process::cgroup::{self, CommandExt};
+
+/// `PATH` of the build scripts.
+pub const BUILD_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/bin:/bin";
 
 #[derive(Debug)]
 pub struct Builder {
     build_dir: PathBuf,
+    /// Language of the build script, `None` for a custom build script.
+    language: Option<Language>,
     script: PathBuf,
     source_file: PathBuf,
     executable_file: PathBuf,
     target_dir: PathBuf,
     timeout: Option<Duration>,
+    cgroups: CgroupsConfig,
 }
 
 impl Builder {
     pub fn new(build_dir: &BuildDir) -> io::Result<Option<Builder>> {
-        let script = {
-            let script = if let Some(ref script) = build_dir.config().build_script {
-                Some(build_dir.join(script))
-            } else if let Some(ref language) = build_dir.config().language {
-                Language::new(language).map(|lang| lang.build_script())
-            } else if let Some(ext) = build_dir.config().source.extension() {
-                Language::from_ext(ext).map(|lang| lang.build_script())
-            } else {
-                None
-            };
-            match script {
-                Some(script) => script,
+        let language = build_dir.language();
+        let script = if let Some(ref script) = build_dir.config().build_script {
+            build_dir.join(script)
+        } else {
+            match language.as_ref() {
+                Some(language) => language.build_script(),
                 None => return Ok(None),
             }
         };
         let script = script.canonicalize()?;
-        if !script.starts_with(&build_dir) {
-            fs::copy(&script, &build_dir.build_script())?;
+        if !script.starts_with(build_dir) {
+            fs::copy(&script, build_dir.build_script())?;
         }
 
         Ok(Some(Builder {
             build_dir: build_dir.as_path().to_owned(),
+            language,
             script,
             source_file: build_dir.config().source.clone(),
             executable_file: build_dir.executable_file(),
             target_dir: build_dir.target_dir(),
             timeout: build_dir.config().timeout,
+            cgroups: build_dir.config().cgroups.clone().unwrap_or_default(),
         }))
     }
 
+    /// Check whether the toolchain of the language is installed.
+    ///
+    /// Return an error message with the missing commands if it is not.
+    pub fn check_toolchain(&self) -> io::Result<Result<(), String>> {
+        let language = match self.language.as_ref() {
+            Some(language) => language,
+            None => return Ok(Ok(())),
+        };
+        let missing_tools = language.missing_tools()?;
+        if missing_tools.is_empty() {
+            Ok(Ok(()))
+        } else {
+            Ok(Err(format!(
+                "The toolchain of language {} is missing: {}",
+                language.name(),
+                missing_tools.join(" ")
+            )))
+        }
+    }
+
     pub async fn build(&self) -> io::Result<BuilderOutput> {
         if !self.target_dir.exists() {
             fs::create_dir(&self.target_dir)?;
         }
         fs::set_permissions(&self.script, Permissions::from_mode(0o700))?;
-        let cg_ctx = cgroup::Builder::new()
-            .cpu_controller(true)
-            .cpuacct_controller(true)
-            .memory_controller(true)
-            .cpuset_controller(true, 1)
-            .build()
-            .await?;
-        #[warn(unused_variables)]
-        let cg_holder = cgroup::ContextHolder {
+        let cg_ctx = self.cgroups.builder().build().await?;
+        let _cg_holder = cgroup::ContextHolder {
             cg: cg_ctx.clone(),
         };
         let res = Command::new("/bin/sh")
@@ -76,7 +90,7 @@ impl Builder {
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .env_clear()
-            .env("PATH", "/usr/local/sbin:/usr/local/bin:/usr/bin:/bin")
+            .env("PATH", BUILD_PATH)
             .env("SOURCE_FILE", &self.source_file)
             .env("EXECUTABLE_FILE", &self.executable_file)
             .env("TARGET_DIR", &self.target_dir)
@@ -0,0 +1,37 @@
+//! Check the environment of the host for judging.
+use std::io::{self, Write};
+
+use ana::language::Language;
+use ana::process::features::Features;
+use clap::{App, ArgMatches, SubCommand};
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("check")
+        .about("Check the isolation features and the language toolchains of the host")
+        .after_help(
+            "The exit status is 0 if programs can be judged with the isolation features \
+             and 1 if they can not.",
+        )
+}
+
+/// Print the report of the host, returning the exit status.
+pub fn execute(_matches: &ArgMatches<'_>) -> io::Result<i32> {
+    let sufficient = report(&mut io::stdout())?;
+    Ok(if sufficient { 0 } else { 1 })
+}
+
+/// Write the isolation features and the languages whose toolchains are found,
+/// returning whether the features are sufficient for judging.
+pub fn report<W: Write>(out: &mut W) -> io::Result<bool> {
+    let features = Features::detect();
+    writeln!(out, "{}", features)?;
+    writeln!(out, "Languages:")?;
+    for language in Language::all()? {
+        match language.missing_tools() {
+            Ok(tools) if tools.is_empty() => writeln!(out, "  {}: available", language.name())?,
+            Ok(tools) => writeln!(out, "  {}: missing {}", language.name(), tools.join(" "))?,
+            Err(e) => writeln!(out, "  {}: invalid ({})", language.name(), e)?,
+        }
+    }
+    Ok(features.is_sufficient())
+}
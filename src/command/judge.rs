@@ -0,0 +1,141 @@
+//! Judge a workspace on the local host.
+use std::io;
+
+use ana::judge::{self, Report, Resource, ResultType};
+use ana::workspace::Workspace;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+/// Exit status if the result of the workspace is not accepted.
+const EXIT_REJECTED: i32 = 1;
+/// Exit status if the workspace fails to be judged.
+const EXIT_FAILED: i32 = 2;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("judge")
+        .about("Judge a workspace on the local host")
+        .after_help(
+            "The cases are named by their directories in the problem, followed by the subtask \
+             which they are judged for. The exit status is 0 if the result is accepted, 1 if it \
+             is not and 2 if the workspace fails to be judged.",
+        )
+        .arg(
+            Arg::with_name("workspace")
+                .value_name("WORKSPACE")
+                .help("The directory of the workspace")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Print the reports as a JSON array"),
+        )
+}
+
+/// Judge the workspace, returning the exit status.
+pub async fn execute(matches: &ArgMatches<'_>) -> io::Result<i32> {
+    let workspace = Workspace::from_path(
+        matches
+            .value_of("workspace")
+            .expect("Failed to get workspace argument"),
+    )?;
+    let json = matches.is_present("json");
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let judging = tokio::spawn(judge::judge(workspace, tx));
+    if !json {
+        println!(
+            "{:<20} {:<22} {:>10} {:>10} {:>12}  Message",
+            "Case", "Result", "Time(ms)", "CPU(ms)", "Memory(KiB)"
+        );
+    }
+    let mut reports = Vec::new();
+    let mut result = None;
+    while let Some(report) = rx.recv().await {
+        let name = match (report.case, report.subtask) {
+            _ if report.summary => {
+                result = Some(report.result.clone());
+                String::from("Total")
+            }
+            (Some(case), Some(subtask)) => format!("Case {} (Subtask {})", case, subtask),
+            (Some(case), None) => format!("Case {}", case),
+            (None, _) => match report.score.as_ref().and_then(|score| score.subtask) {
+                Some(subtask) => format!("Subtask {}", subtask),
+                None => String::new(),
+            },
+        };
+        if json {
+            reports.push(json_report(&name, &report));
+        } else {
+            print_report(&name, &report);
+        }
+    }
+    if json {
+        println!("{}", Value::Array(reports));
+    }
+    if let Err(e) = judging.await? {
+        eprintln!("Failed to judge the workspace: {}", e);
+        return Ok(EXIT_FAILED);
+    }
+    Ok(match result {
+        Some(ResultType::Accepted) => 0,
+        Some(ResultType::SystemError) | None => EXIT_FAILED,
+        Some(_) => EXIT_REJECTED,
+    })
+}
+
+fn print_report(name: &str, report: &Report) {
+    let (time, cpu_time, memory) = match report.usage {
+        Some(usage) => (
+            usage.real_time.as_millis().to_string(),
+            usage.cpu_time.as_millis().to_string(),
+            (usage.memory / 1024).to_string(),
+        ),
+        None => (String::from("-"), String::from("-"), String::from("-")),
+    };
+    let mut result = format!("{:?}", report.result);
+    if let Some(score) = report.score {
+        result = format!("{} {}/{}", result, score.score, score.full_score);
+    }
+    // only the first line of a long message such as a compile error is in the table
+    let mut lines = report.message.lines();
+    println!(
+        "{:<20} {:<22} {:>10} {:>10} {:>12}  {}",
+        name,
+        result,
+        time,
+        cpu_time,
+        memory,
+        lines.next().unwrap_or_default()
+    );
+    if report.summary && lines.next().is_some() {
+        println!("\n{}", report.message);
+    }
+}
+
+fn json_report(name: &str, report: &Report) -> Value {
+    json!({
+        "name": name,
+        "result": format!("{:?}", report.result),
+        "usage": report.usage.as_ref().map(json_resource),
+        "interactor_usage": report.interactor_usage.as_ref().map(json_resource),
+        "case": report.case,
+        "subtask": report.subtask,
+        "score": report.score.map(|score| json!({
+            "subtask": score.subtask,
+            "score": score.score,
+            "full_score": score.full_score,
+        })),
+        "message": report.message,
+        "summary": report.summary,
+    })
+}
+
+fn json_resource(usage: &Resource) -> Value {
+    json!({
+        "real_time_ms": usage.real_time.as_secs_f64() * 1000.0,
+        "cpu_time_ms": usage.cpu_time.as_secs_f64() * 1000.0,
+        "memory": usage.memory,
+    })
+}
@@ -0,0 +1,54 @@
+//! List the languages which can be used by the tasks.
+use std::io;
+
+use ana::language::Language;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_json::{json, Value};
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("languages")
+        .about("List the languages in the language directories")
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Print the languages as a JSON array"),
+        )
+}
+
+/// Print the languages, returning the exit status.
+pub fn execute(matches: &ArgMatches<'_>) -> io::Result<i32> {
+    let json = matches.is_present("json");
+    let mut languages = Vec::new();
+    if !json {
+        println!(
+            "{:<16} {:<10} {:<8} {:<40}  Missing tools",
+            "Language", "Extension", "Default", "Version"
+        );
+    }
+    for language in Language::all()? {
+        let version = language.version();
+        let missing_tools = language.missing_tools()?;
+        if json {
+            languages.push(json!({
+                "id": language.name(),
+                "extension": language.extension(),
+                "default": language.is_default(),
+                "version": version,
+                "missing_tools": missing_tools,
+            }));
+        } else {
+            println!(
+                "{:<16} {:<10} {:<8} {:<40}  {}",
+                language.name(),
+                language.extension(),
+                if language.is_default() { "yes" } else { "no" },
+                version.as_deref().unwrap_or("-"),
+                missing_tools.join(" ")
+            );
+        }
+    }
+    if json {
+        println!("{}", Value::Array(languages));
+    }
+    Ok(0)
+}
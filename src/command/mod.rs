@@ -0,0 +1,5 @@
+//! Subcommands of the command line interface besides the server.
+pub mod check;
+pub mod judge;
+pub mod languages;
+pub mod run;
@@ -0,0 +1,184 @@
+//! Run a single program in the sandbox of the judge.
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use ana::judge::{self, Execution};
+use ana::workspace::problem::ResourceLimit;
+use ana::workspace::{RunnerConfig, RuntimeDir, RuntimeHolder};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use nix::sys::signal::Signal;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("run")
+        .about("Run a program in the sandbox of the judge")
+        .after_help(
+            "The program is copied into the runtime directory as `main`, or all the files in it \
+             are copied if it is a directory. The exit status is 0 if the program exits normally \
+             within the limits and 1 if it does not.",
+        )
+        .arg(
+            Arg::with_name("program")
+                .value_name("PROGRAM")
+                .help("The executable file or the directory of the program")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("config")
+                .takes_value(true)
+                .value_name("FILE")
+                .long("config")
+                .short("c")
+                .help("The runner config, or a workspace config with a [runner] section"),
+        )
+        .arg(
+            Arg::with_name("stdin")
+                .takes_value(true)
+                .value_name("FILE")
+                .long("stdin")
+                .short("i")
+                .help("The input file of the program [default: /dev/null]"),
+        )
+        .arg(
+            Arg::with_name("stdout")
+                .takes_value(true)
+                .value_name("FILE")
+                .long("stdout")
+                .short("o")
+                .help("The output file of the program [default: stdout]"),
+        )
+        .arg(
+            Arg::with_name("time_limit")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .long("time-limit")
+                .short("t")
+                .help("The limit of the CPU time and the wall time")
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("memory_limit")
+                .takes_value(true)
+                .value_name("MIB")
+                .long("memory-limit")
+                .short("m")
+                .help("The limit of the memory")
+                .default_value("256"),
+        )
+        .arg(
+            Arg::with_name("output_limit")
+                .takes_value(true)
+                .value_name("MIB")
+                .long("output-limit")
+                .help("The limit of the output file"),
+        )
+        .arg(
+            Arg::with_name("processes")
+                .takes_value(true)
+                .value_name("N")
+                .long("processes")
+                .help("The limit of the processes and threads"),
+        )
+}
+
+/// Run the program, returning the exit status.
+pub async fn execute(matches: &ArgMatches<'_>) -> io::Result<i32> {
+    let program = Path::new(
+        matches
+            .value_of("program")
+            .expect("Failed to get program argument"),
+    );
+    let config = match matches.value_of("config") {
+        Some(path) => runner_config(Path::new(path))?,
+        None => RunnerConfig::default(),
+    };
+    let time_limit: f64 = matches
+        .value_of("time_limit")
+        .expect("Failed to get time_limit argument")
+        .parse()
+        .expect("`time_limit` argument is invalid");
+    let memory_limit: usize = matches
+        .value_of("memory_limit")
+        .expect("Failed to get memory_limit argument")
+        .parse()
+        .expect("`memory_limit` argument is invalid");
+    let output_limit = matches.value_of("output_limit").map(|output_limit| {
+        output_limit
+            .parse::<usize>()
+            .expect("`output_limit` argument is invalid")
+            * 1024
+            * 1024
+    });
+    let processes = matches
+        .value_of("processes")
+        .map(|processes| processes.parse().expect("`processes` argument is invalid"));
+    let limit = ResourceLimit {
+        cpu_time: Duration::from_secs_f64(time_limit),
+        real_time: Duration::from_secs_f64(time_limit),
+        memory: memory_limit * 1024 * 1024,
+        output: output_limit,
+        processes,
+    };
+    let stdin = File::open(matches.value_of("stdin").unwrap_or("/dev/null"))?;
+    let stdout = match matches.value_of("stdout") {
+        Some(path) => Stdio::from(File::create(path)?),
+        None => Stdio::inherit(),
+    };
+
+    // the work directory of the rootfs is placed next to the runtime directory
+    let dir = tempfile::Builder::new().prefix("ana-run-").tempdir()?;
+    let runtime_dir = RuntimeDir::from_path(dir.path().join("runtime"));
+    let _runtime_holder = RuntimeHolder::new(&runtime_dir, config.rootfs.as_ref())?;
+    if program.is_dir() {
+        for entry in program.read_dir()? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                fs::copy(entry.path(), runtime_dir.join(entry.file_name()))?;
+            }
+        }
+    } else {
+        fs::copy(program, runtime_dir.executable_file())?;
+    }
+
+    let execution =
+        judge::run_program(&runtime_dir, &config, &limit, Stdio::from(stdin), stdout).await?;
+    print_execution(&execution);
+    Ok(if execution.result.is_none() { 0 } else { 1 })
+}
+
+/// Load the runner config from the `[runner]` section or the whole file.
+fn runner_config(path: &Path) -> io::Result<RunnerConfig> {
+    let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+    let mut config: toml::Value = toml::from_str(&fs::read_to_string(path)?).map_err(invalid)?;
+    if let Some(runner) = config.get_mut("runner") {
+        config = runner.clone();
+    }
+    config.try_into().map_err(invalid)
+}
+
+fn print_execution(execution: &Execution) {
+    match execution.result.as_ref() {
+        Some(result) => eprintln!("Result: {:?}", result),
+        None => eprintln!("Result: Exited normally"),
+    }
+    match execution.exit_status.signal() {
+        Some(signal) => match Signal::try_from(signal) {
+            Ok(name) => eprintln!("Signal: {} ({})", signal, name),
+            Err(_) => eprintln!("Signal: {}", signal),
+        },
+        None => eprintln!(
+            "Exit status: {}",
+            execution.exit_status.code().unwrap_or_default()
+        ),
+    }
+    eprintln!("CPU time: {} ms", execution.usage.cpu_time.as_millis());
+    eprintln!("Wall time: {} ms", execution.usage.real_time.as_millis());
+    eprintln!("Peak memory: {} KiB", execution.usage.memory / 1024);
+    if !execution.message.is_empty() {
+        eprintln!("Message: {}", execution.message);
+    }
+}
@@ -1,15 +1,87 @@
-//! Utils to compare two files/strings line by line.
+//! Utils to compare two files/strings line by line or token by token.
 use std::char;
+use std::fmt;
 use std::io;
 use std::marker::Unpin;
 use std::path::Path;
+use std::pin::Pin;
+use std::task::Poll;
 
+use futures::future;
 use tokio::fs::File;
 use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
 
+/// Max length of an excerpt shown in the difference message.
+const MAX_EXCERPT_LENGTH: usize = 64;
+
+/// Bytes shown before the first different byte in a line.
+const EXCERPT_CONTEXT_LENGTH: usize = 16;
+
+/// Max bytes of a token read at once, so a huge token is not loaded into memory.
+///
+/// Longer tokens are compared byte by byte in chunks.
+const TOKEN_CHUNK_LENGTH: usize = 4096;
+
 pub struct Comparer {
     ignore_white_space_at_eol: bool,
     ignore_empty_line_at_eof: bool,
+    mode: Mode,
+}
+
+/// The way to compare the output and the answer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Compare line by line.
+    Line,
+    /// Compare token by token and ignore the layout of white spaces.
+    Token,
+    /// Compare token by token and accept numeric tokens within the given errors.
+    ///
+    /// A number is accepted if either the absolute error or the
+    /// relative error to the answer is not greater than the limit.
+    Float {
+        absolute_error: f64,
+        relative_error: f64,
+    },
+}
+
+/// The first difference between the output and the answer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch {
+    /// Line number of the difference in the output, starting from 1.
+    pub line: usize,
+    /// Column of the first different byte in the line mode, starting from 1.
+    pub column: Option<usize>,
+    /// Index of the different token in the token modes, starting from 1.
+    pub token: Option<usize>,
+    /// Excerpt of the answer or `None` at the end of the answer.
+    pub expected: Option<String>,
+    /// Excerpt of the output or `None` at the end of the output.
+    pub found: Option<String>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.token, self.column) {
+            (Some(token), _) => write!(f, "Token {} on line {}", token, self.line)?,
+            (None, Some(column)) => write!(f, "Line {}, column {}", self.line, column)?,
+            (None, None) => write!(f, "Line {}", self.line)?,
+        }
+        write!(
+            f,
+            ": expected {} but found {}",
+            describe(&self.expected, "answer"),
+            describe(&self.found, "output")
+        )
+    }
+}
+
+/// Describe the excerpt of `side`, which is either the output or the answer.
+fn describe(excerpt: &Option<String>, side: &str) -> String {
+    match excerpt {
+        Some(excerpt) => format!("`{}`", excerpt),
+        None => format!("the end of {}", side),
+    }
 }
 
 impl Comparer {
@@ -17,10 +89,17 @@ impl Comparer {
         Comparer {
             ignore_white_space_at_eol,
             ignore_empty_line_at_eof,
+            mode: Mode::Line,
         }
     }
 
-    /// Compare output file and answer file line by line.
+    /// Set the compare mode of the comparer.
+    pub fn mode(mut self, mode: Mode) -> Comparer {
+        self.mode = mode;
+        self
+    }
+
+    /// Compare output file and answer file.
     ///
     /// Return `true` if there is no difference between two files.
     pub async fn compare_files(
@@ -28,11 +107,23 @@ impl Comparer {
         output_file: impl AsRef<Path>,
         answer_file: impl AsRef<Path>,
     ) -> io::Result<bool> {
+        Ok(self.diff_files(output_file, answer_file).await?.is_none())
+    }
+
+    /// Compare output file and answer file.
+    ///
+    /// Return the first difference
+    /// or `None` if there is no difference between two files.
+    pub async fn diff_files(
+        &self,
+        output_file: impl AsRef<Path>,
+        answer_file: impl AsRef<Path>,
+    ) -> io::Result<Option<Mismatch>> {
         let output_file = File::open(&output_file).await?;
         let answer_file = File::open(&answer_file).await?;
         let mut output_buf = BufReader::new(output_file);
         let mut answer_buf = BufReader::new(answer_file);
-        Ok(!self.buf_diff(&mut output_buf, &mut answer_buf).await?)
+        self.diff(&mut output_buf, &mut answer_buf).await
     }
 
     /// Compare two bytes.
@@ -41,27 +132,128 @@ impl Comparer {
     pub async fn compare<S: AsRef<[u8]>>(&self, output: S, answer: S) -> bool {
         let mut output_buf = BufReader::new(output.as_ref());
         let mut answer_buf = BufReader::new(answer.as_ref());
-        !self
-            .buf_diff(&mut output_buf, &mut answer_buf)
+        self.diff(&mut output_buf, &mut answer_buf)
             .await
             .unwrap()
+            .is_none()
     }
 }
 
 impl Comparer {
+    /// Find the first difference between two buffers by the compare mode.
+    async fn diff<'a>(
+        &self,
+        output_buf: &'a mut (dyn AsyncBufRead + Unpin + Send + Sync),
+        answer_buf: &'a mut (dyn AsyncBufRead + Unpin + Send + Sync),
+    ) -> io::Result<Option<Mismatch>> {
+        match self.mode {
+            Mode::Line => self.buf_diff(output_buf, answer_buf).await,
+            Mode::Token => {
+                self.token_diff(output_buf, answer_buf, |output, answer| output == answer)
+                    .await
+            }
+            Mode::Float {
+                absolute_error,
+                relative_error,
+            } => {
+                self.token_diff(output_buf, answer_buf, |output, answer| {
+                    float_eq(output, answer, absolute_error, relative_error)
+                })
+                .await
+            }
+        }
+    }
+
+    /// Check if two buffers is equal token by token.
+    ///
+    /// Tokens are separated by any ASCII white space and compared by the `eq` function,
+    /// except that the tokens longer than `TOKEN_CHUNK_LENGTH` are compared byte by byte.
+    ///
+    /// Return the first different token
+    /// or `None` if there is no difference between two buffers.
+    async fn token_diff<'a, F>(
+        &self,
+        output_buf: &'a mut (dyn AsyncBufRead + Unpin + Send + Sync),
+        answer_buf: &'a mut (dyn AsyncBufRead + Unpin + Send + Sync),
+        eq: F,
+    ) -> io::Result<Option<Mismatch>>
+    where
+        F: Fn(&[u8], &[u8]) -> bool,
+    {
+        let mut output_tokens = Tokens::new(output_buf);
+        let mut answer_tokens = Tokens::new(answer_buf);
+        let mut index = 0;
+        loop {
+            index += 1;
+            let has_output = output_tokens.skip_white_spaces().await?;
+            let has_answer = answer_tokens.skip_white_spaces().await?;
+            let mut output = Vec::new();
+            let mut answer = Vec::new();
+            let mut output_end = true;
+            let mut answer_end = true;
+            if has_output {
+                output_end = output_tokens
+                    .read_token(&mut output, TOKEN_CHUNK_LENGTH)
+                    .await?;
+            }
+            if has_answer {
+                answer_end = answer_tokens
+                    .read_token(&mut answer, TOKEN_CHUNK_LENGTH)
+                    .await?;
+            }
+            let (position, skipped) = match (has_output, has_answer) {
+                (false, false) => return Ok(None),
+                (true, true) if output_end && answer_end => {
+                    if eq(&output, &answer) {
+                        continue;
+                    }
+                    (0, 0)
+                }
+                (true, true) => match diff_long_token(
+                    (&mut output_tokens, &mut output, output_end),
+                    (&mut answer_tokens, &mut answer, answer_end),
+                )
+                .await?
+                {
+                    Some(difference) => difference,
+                    None => continue,
+                },
+                _ => (0, 0),
+            };
+            return Ok(Some(Mismatch {
+                line: output_tokens.token_line(),
+                column: None,
+                token: Some(index),
+                expected: if has_answer {
+                    Some(token_excerpt(&answer, position, skipped))
+                } else {
+                    None
+                },
+                found: if has_output {
+                    Some(token_excerpt(&output, position, skipped))
+                } else {
+                    None
+                },
+            }));
+        }
+    }
+
     /// Check if two buffers is equal line by line.
     ///
     /// Handle the blank line at the end of file
     /// and white space at the end of line
     /// by the checker's options.
     ///
-    /// Return `false` if there is no difference between two buffers.
+    /// Return the first different line
+    /// or `None` if there is no difference between two buffers.
     async fn buf_diff<'a>(
         &self,
         output_buf: &'a mut (dyn AsyncBufRead + Unpin + Send + Sync),
         answer_buf: &'a mut (dyn AsyncBufRead + Unpin + Send + Sync),
-    ) -> io::Result<bool> {
+    ) -> io::Result<Option<Mismatch>> {
+        let mut line = 0;
         loop {
+            line += 1;
             let output = {
                 let mut buf = Vec::new();
                 output_buf.read_until(b'\n', &mut buf).await?;
@@ -84,35 +276,46 @@ impl Comparer {
             let mut output = output.as_deref();
             let mut answer = answer.as_deref();
             if self.ignore_white_space_at_eol {
-                output = output.map(|s| trim_end(s));
-                answer = answer.map(|s| trim_end(s));
+                output = output.map(trim_end);
+                answer = answer.map(trim_end);
             }
 
-            match (output, answer) {
+            let column = match (output, answer) {
                 (Some(output), Some(answer)) => {
-                    if output != answer {
-                        return Ok(true);
+                    if output == answer {
+                        continue;
                     }
-                    continue;
+                    output
+                        .iter()
+                        .zip(answer)
+                        .take_while(|(output, answer)| output == answer)
+                        .count()
                 }
                 (Some(output), None) => {
-                    if !output.is_empty() || !self.ignore_empty_line_at_eof {
-                        return Ok(true);
+                    if output.is_empty() && self.ignore_empty_line_at_eof {
+                        continue;
                     }
-                    continue;
+                    0
                 }
                 (None, Some(answer)) => {
-                    if !answer.is_empty() || !self.ignore_empty_line_at_eof {
-                        return Ok(true);
+                    if answer.is_empty() && self.ignore_empty_line_at_eof {
+                        continue;
                     }
-                    continue;
+                    0
                 }
                 (None, None) => {
                     break;
                 }
-            }
+            };
+            return Ok(Some(Mismatch {
+                line,
+                column: Some(column + 1),
+                token: None,
+                expected: answer.map(|answer| excerpt(answer, column)),
+                found: output.map(|output| excerpt(output, column)),
+            }));
         }
-        Ok(false)
+        Ok(None)
     }
 }
 
@@ -133,6 +336,196 @@ fn trim_end(s: &[u8]) -> &[u8] {
     &s[0..last]
 }
 
+/// Check if two tokens are equal numbers within the given errors.
+///
+/// Tokens which are not numbers are compared byte by byte.
+fn float_eq(output: &[u8], answer: &[u8], absolute_error: f64, relative_error: f64) -> bool {
+    if output == answer {
+        return true;
+    }
+    let (output, answer) = match (parse_float(output), parse_float(answer)) {
+        (Some(output), Some(answer)) => (output, answer),
+        _ => return false,
+    };
+    if !answer.is_finite() || !output.is_finite() {
+        return output == answer || (output.is_nan() && answer.is_nan());
+    }
+    let error = (output - answer).abs();
+    error <= absolute_error || error <= relative_error * answer.abs()
+}
+
+fn parse_float(token: &[u8]) -> Option<f64> {
+    std::str::from_utf8(token).ok()?.parse().ok()
+}
+
+/// Truncate the bytes around the position to a safe length for showing.
+fn excerpt(s: &[u8], position: usize) -> String {
+    let s = s.strip_suffix(b"\n").unwrap_or(s);
+    let start = position.saturating_sub(EXCERPT_CONTEXT_LENGTH).min(s.len());
+    let end = s.len().min(start + MAX_EXCERPT_LENGTH);
+    format!(
+        "{}{}{}",
+        if start > 0 { "..." } else { "" },
+        String::from_utf8_lossy(&s[start..end]),
+        if end < s.len() { "..." } else { "" }
+    )
+}
+
+/// Compare the rest of two long tokens byte by byte in chunks.
+///
+/// Each side is the reader, the part of the token read from it and whether the token ends.
+/// Return the position of the first different byte in the parts with the bytes dropped
+/// before them, or `None` if the tokens are equal.
+async fn diff_long_token(
+    (output_tokens, output, mut output_end): (&mut Tokens<'_>, &mut Vec<u8>, bool),
+    (answer_tokens, answer, mut answer_end): (&mut Tokens<'_>, &mut Vec<u8>, bool),
+) -> io::Result<Option<(usize, usize)>> {
+    let mut skipped = 0;
+    loop {
+        let same = output
+            .iter()
+            .zip(answer.iter())
+            .take_while(|(output, answer)| output == answer)
+            .count();
+        let output_done = output_end && same == output.len();
+        let answer_done = answer_end && same == answer.len();
+        if output_done && answer_done {
+            return Ok(None);
+        }
+        if output_done || answer_done || same < output.len().min(answer.len()) {
+            return Ok(Some((same, skipped)));
+        }
+        // the bytes before the difference are kept for the excerpt
+        let dropped = same - same.min(EXCERPT_CONTEXT_LENGTH);
+        output.drain(..dropped);
+        answer.drain(..dropped);
+        skipped += dropped;
+        if !output_end {
+            let max = TOKEN_CHUNK_LENGTH - output.len();
+            output_end = output_tokens.read_token(output, max).await?;
+        }
+        if !answer_end {
+            let max = TOKEN_CHUNK_LENGTH - answer.len();
+            answer_end = answer_tokens.read_token(answer, max).await?;
+        }
+    }
+}
+
+/// Excerpt of a part of a token, after which `skipped` bytes of the token are dropped.
+fn token_excerpt(token: &[u8], position: usize, skipped: usize) -> String {
+    let excerpt = excerpt(token, position);
+    if skipped > 0 && !excerpt.starts_with("...") {
+        format!("...{}", excerpt)
+    } else {
+        excerpt
+    }
+}
+
+/// Read white space separated tokens from a buffer.
+struct Tokens<'a> {
+    inner: &'a mut (dyn AsyncBufRead + Unpin + Send + Sync),
+    /// Current line number of the buffer.
+    line: usize,
+    /// Line number of the last token.
+    token_line: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(inner: &'a mut (dyn AsyncBufRead + Unpin + Send + Sync)) -> Tokens<'a> {
+        Tokens {
+            inner,
+            line: 1,
+            token_line: 1,
+        }
+    }
+
+    /// Line number of the last token or the end of the buffer.
+    fn token_line(&self) -> usize {
+        self.token_line
+    }
+
+    /// Skip the white spaces before the next token.
+    ///
+    /// Return `false` at the end of the buffer.
+    async fn skip_white_spaces(&mut self) -> io::Result<bool> {
+        loop {
+            let inner = &mut self.inner;
+            let line = &mut self.line;
+            let token_line = &mut self.token_line;
+            let (consumed, found) = future::poll_fn(|cx| {
+                let buf = match Pin::new(&mut **inner).poll_fill_buf(cx) {
+                    Poll::Ready(Ok(buf)) => buf,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => return Poll::Pending,
+                };
+                // the end of the buffer
+                if buf.is_empty() {
+                    *token_line = *line;
+                    return Poll::Ready(Ok((0, Some(false))));
+                }
+                let mut consumed = 0;
+                for &c in buf {
+                    if !c.is_ascii_whitespace() {
+                        *token_line = *line;
+                        return Poll::Ready(Ok((consumed, Some(true))));
+                    }
+                    consumed += 1;
+                    if c == b'\n' {
+                        *line += 1;
+                    }
+                }
+                Poll::Ready(Ok((consumed, None)))
+            })
+            .await?;
+            Pin::new(&mut *self.inner).consume(consumed);
+            if let Some(found) = found {
+                return Ok(found);
+            }
+        }
+    }
+
+    /// Append at most `max` bytes of the current token to `token`.
+    ///
+    /// Return `true` if the token ends.
+    async fn read_token(&mut self, token: &mut Vec<u8>, max: usize) -> io::Result<bool> {
+        let mut rest = max;
+        loop {
+            let inner = &mut self.inner;
+            let rest = &mut rest;
+            let token = &mut *token;
+            let (consumed, finished) = future::poll_fn(|cx| {
+                let buf = match Pin::new(&mut **inner).poll_fill_buf(cx) {
+                    Poll::Ready(Ok(buf)) => buf,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => return Poll::Pending,
+                };
+                // the end of the buffer
+                if buf.is_empty() {
+                    return Poll::Ready(Ok((0, Some(true))));
+                }
+                let mut consumed = 0;
+                for &c in buf {
+                    if c.is_ascii_whitespace() {
+                        return Poll::Ready(Ok((consumed, Some(true))));
+                    }
+                    if *rest == 0 {
+                        return Poll::Ready(Ok((consumed, Some(false))));
+                    }
+                    consumed += 1;
+                    *rest -= 1;
+                    token.push(c);
+                }
+                Poll::Ready(Ok((consumed, None)))
+            })
+            .await?;
+            Pin::new(&mut *self.inner).consume(consumed);
+            if let Some(finished) = finished {
+                return Ok(finished);
+            }
+        }
+    }
+}
+
 /// Default comparer which compare two files/strings/bytes line by line
 /// and ignore white space at line end and empty line at file end.
 impl Default for Comparer {
@@ -153,6 +546,26 @@ mod tests {
         !block_on(Comparer::default().compare(&output, &answer))
     }
 
+    fn float_diff(output: &[u8], answer: &[u8]) -> Option<String> {
+        mode_diff(
+            Mode::Float {
+                absolute_error: 1e-6,
+                relative_error: 1e-6,
+            },
+            output,
+            answer,
+        )
+    }
+
+    fn mode_diff(mode: Mode, output: &[u8], answer: &[u8]) -> Option<String> {
+        let comparer = Comparer::default().mode(mode);
+        let mut output_buf = BufReader::new(output);
+        let mut answer_buf = BufReader::new(answer);
+        block_on(comparer.diff(&mut output_buf, &mut answer_buf))
+            .unwrap()
+            .map(|mismatch| mismatch.to_string())
+    }
+
     #[tokio::test]
     async fn test_diff_complete_eq() {
         assert!(!diff(b"hello world", b"hello world"));
@@ -190,6 +603,116 @@ mod tests {
         assert!(!diff(b"hello world\n", b"hello world\n \n"));
     }
 
+    #[tokio::test]
+    async fn test_float_diff() {
+        assert_eq!(float_diff(b"0.3333333", b"0.333333333"), None);
+        assert_eq!(float_diff(b"1e9 2", b"1000000001 2.0000001"), None);
+        assert_eq!(float_diff(b"1 2\n3", b"1.0 2.0 3.0\n"), None);
+        assert_eq!(float_diff(b"inf nan", b"inf nan"), None);
+        assert_eq!(float_diff(b"Yes 0.5", b"Yes 0.5"), None);
+        assert_eq!(
+            float_diff(b"1.0 0.33", b"1.0 0.333333"),
+            Some(String::from(
+                "Token 2 on line 1: expected `0.333333` but found `0.33`"
+            ))
+        );
+        assert!(float_diff(b"No", b"Yes").is_some());
+        assert!(float_diff(b"1 2", b"1").is_some());
+        assert!(float_diff(b"1", b"1 2").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_token_diff() {
+        assert_eq!(mode_diff(Mode::Token, b"1 2 3", b"1\n2\n3\n"), None);
+        assert_eq!(mode_diff(Mode::Token, b"  1\t2\n\n3  ", b"1 2 3"), None);
+        assert_eq!(mode_diff(Mode::Token, b"", b"\n\n"), None);
+        assert_eq!(
+            mode_diff(Mode::Token, b"1 2 3", b"1 2 3.0"),
+            Some(String::from(
+                "Token 3 on line 1: expected `3.0` but found `3`"
+            ))
+        );
+        assert!(mode_diff(Mode::Token, b"hello world", b"helloworld").is_some());
+        assert_eq!(
+            mode_diff(Mode::Token, b"1\n2\n\n", b"1 2 3"),
+            Some(String::from(
+                "Token 3 on line 4: expected `3` but found the end of output"
+            ))
+        );
+        assert_eq!(
+            mode_diff(Mode::Token, b"1\n 2", b"1"),
+            Some(String::from(
+                "Token 2 on line 2: expected the end of answer but found `2`"
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_long_token_diff() {
+        // tokens longer than a chunk are compared chunk by chunk
+        let long = vec![b'9'; TOKEN_CHUNK_LENGTH * 3 + 5];
+        let output = [&b"1 "[..], &long, b" 2"].concat();
+        assert_eq!(mode_diff(Mode::Token, &output, &output), None);
+        assert!(mode_diff(Mode::Token, &output, &output[..output.len() - 2]).is_some());
+
+        let mut answer = output.clone();
+        answer[2 + TOKEN_CHUNK_LENGTH * 2 + 100] = b'8';
+        assert_eq!(
+            mode_diff(Mode::Token, &output, &answer),
+            Some(format!(
+                "Token 2 on line 1: expected `...{}8{}...` but found `...{}...`",
+                "9".repeat(16),
+                "9".repeat(47),
+                "9".repeat(64)
+            ))
+        );
+        // a longer output is wrong even if the answer is its prefix
+        let answer = [&b"1 "[..], &long[..TOKEN_CHUNK_LENGTH * 2], b" 2"].concat();
+        assert!(mode_diff(Mode::Token, &output, &answer).is_some());
+        assert!(mode_diff(Mode::Token, &answer, &output).is_some());
+        assert!(float_diff(&output, &answer).is_some());
+        assert_eq!(float_diff(&output, &output), None);
+    }
+
+    #[tokio::test]
+    async fn test_line_diff() {
+        assert_eq!(
+            mode_diff(Mode::Line, b"hello\nworld\n", b"hello\nword\n"),
+            Some(String::from(
+                "Line 2, column 4: expected `word` but found `world`"
+            ))
+        );
+        assert_eq!(
+            mode_diff(Mode::Line, b"hello\n", b"hello\nworld\n"),
+            Some(String::from(
+                "Line 2, column 1: expected `world` but found the end of output"
+            ))
+        );
+        assert_eq!(
+            mode_diff(Mode::Line, b"hello\nworld\n", b"hello\n"),
+            Some(String::from(
+                "Line 2, column 1: expected the end of answer but found `world`"
+            ))
+        );
+        let output = [b'a'; 100];
+        let mut answer = [b'a'; 100];
+        answer[50] = b'b';
+        let mismatch = block_on(Comparer::default().diff(
+            &mut BufReader::new(&output[..]),
+            &mut BufReader::new(&answer[..]),
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(mismatch.line, 1);
+        assert_eq!(mismatch.column, Some(51));
+        assert_eq!(mismatch.token, None);
+        assert_eq!(
+            mismatch.expected,
+            Some(format!("...{}b{}...", "a".repeat(16), "a".repeat(47)))
+        );
+        assert_eq!(mismatch.found, Some(format!("...{}...", "a".repeat(64))));
+    }
+
     #[tokio::test]
     async fn test_check() -> io::Result<()> {
         let work_dir = tempfile::tempdir()?;
@@ -1,8 +1,13 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::io;
 use std::io::Read;
 use std::os::unix::fs as unix_fs;
-use std::process::{Command, Stdio};
+use std::os::unix::io::FromRawFd;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use log;
@@ -10,13 +15,13 @@ use tokio::fs;
 use tokio::sync::mpsc;
 
 use crate::builder::Builder;
-use crate::comparer::Comparer;
+use crate::comparer::{self, Comparer};
 use crate::process::*;
-use crate::runner::Runner;
+use crate::runner::{Program, Runner};
 use crate::workspace::{
     build::BuildDir,
-    problem::{ProblemType, ResourceLimit},
-    runtime::RuntimeHolder,
+    problem::{Case, CompareMode, ProblemType, ResourceLimit, Scoring, Subtask},
+    runtime::{RunnerConfig, RuntimeDir, RuntimeHolder},
     Workspace,
 };
 
@@ -25,6 +30,16 @@ pub struct Report {
     pub result: ResultType,
     pub usage: Option<Resource>,
     pub message: String,
+    /// Resource usage of the interactor when judging an interactive problem.
+    pub interactor_usage: Option<Resource>,
+    /// Score of a subtask or the whole problem.
+    pub score: Option<Score>,
+    /// Index of the judged case, which is the name of its directory in the problem.
+    pub case: Option<usize>,
+    /// Index of the subtask which the case is judged for if the problem has subtasks.
+    pub subtask: Option<usize>,
+    /// Whether this is the last report of the task which summarizes all the cases.
+    pub summary: bool,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
@@ -33,9 +48,13 @@ pub enum ResultType {
     WrongAnswer,
     TimeLimitExceeded,
     MemoryLimitExceeded,
+    OutputLimitExceeded,
+    RestrictedFunction,
     RuntimeError,
     CompileError,
     SystemError,
+    /// The task is cancelled before it is finished.
+    Cancelled,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -45,6 +64,25 @@ pub struct Resource {
     pub memory: usize,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Score {
+    /// Index of the subtask, `None` for the whole problem.
+    pub subtask: Option<usize>,
+    pub score: f64,
+    pub full_score: f64,
+}
+
+impl Resource {
+    /// The maximum of each kind of resource usage.
+    fn max(&self, other: &Resource) -> Resource {
+        Resource {
+            real_time: self.real_time.max(other.real_time),
+            cpu_time: self.cpu_time.max(other.cpu_time),
+            memory: self.memory.max(other.memory),
+        }
+    }
+}
+
 impl From<ResourceLimit> for Resource {
     fn from(r: ResourceLimit) -> Resource {
         Resource {
@@ -55,9 +93,123 @@ impl From<ResourceLimit> for Resource {
     }
 }
 
+impl Report {
+    pub fn new(result: ResultType, usage: Option<Resource>, message: String) -> Report {
+        Report {
+            result,
+            usage,
+            message,
+            interactor_usage: None,
+            score: None,
+            case: None,
+            subtask: None,
+            summary: false,
+        }
+    }
+
+    /// Mark the report as the last report of the task.
+    pub fn into_summary(mut self) -> Report {
+        self.summary = true;
+        self
+    }
+}
+
+/// Handle for cancelling a judge task.
+///
+/// Cancelling a task kills the programs which are running in it and
+/// stops it before the next case with an `Interrupted` error.
+#[derive(Clone, Default)]
+pub struct Cancellation {
+    inner: Arc<CancellationState>,
+}
+
+#[derive(Default)]
+struct CancellationState {
+    cancelled: AtomicBool,
+    next_id: AtomicUsize,
+    cgroups: Mutex<HashMap<usize, cgroup::Context>>,
+}
+
+impl Cancellation {
+    pub fn new() -> Cancellation {
+        Default::default()
+    }
+
+    /// Cancel the task and kill the programs which are running.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        for cg in self.inner.cgroups.lock().unwrap().values() {
+            if let Err(e) = cg.kill() {
+                log::warn!("Failed to kill the programs of a cancelled task. {}", e);
+            }
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Return an `Interrupted` error if the task is cancelled.
+    fn check(&self) -> io::Result<()> {
+        if self.is_cancelled() {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "The task is cancelled",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Kill the program when the task is cancelled until the returned guard is dropped.
+    fn watch(&self, program: &Program) -> CancellationGuard {
+        let id = self.inner.next_id.fetch_add(1, Ordering::SeqCst);
+        let cg = program.cgroup().clone();
+        self.inner.cgroups.lock().unwrap().insert(id, cg.clone());
+        // the task may be cancelled before the program is watched
+        if self.is_cancelled() {
+            let _ = cg.kill();
+        }
+        CancellationGuard {
+            cancellation: self.clone(),
+            id,
+        }
+    }
+}
+
+struct CancellationGuard {
+    cancellation: Cancellation,
+    id: usize,
+}
+
+impl Drop for CancellationGuard {
+    fn drop(&mut self) {
+        let mut cgroups = self.cancellation.inner.cgroups.lock().unwrap();
+        cgroups.remove(&self.id);
+    }
+}
+
+/// Default max absolute and relative error in the `Float` compare mode.
+const DEFAULT_FLOAT_ERROR: f64 = 1e-6;
+
+/// The way to check the output of the program for each case.
+enum Checker {
+    Comparer(Comparer),
+    SpecialJudge(BuildDir),
+    Interactor(BuildDir),
+}
+
 pub async fn judge(
     workspace: Workspace,
     reporter: mpsc::UnboundedSender<Report>,
+) -> io::Result<()> {
+    judge_cancellable(workspace, reporter, &Cancellation::new()).await
+}
+
+/// Judge the workspace until it is finished or cancelled by `cancellation`.
+pub async fn judge_cancellable(
+    workspace: Workspace,
+    reporter: mpsc::UnboundedSender<Report>,
+    cancellation: &Cancellation,
 ) -> io::Result<()> {
     log::debug!("Start judging workspace {}", workspace.as_path().display());
     log::debug!(
@@ -67,284 +219,725 @@ pub async fn judge(
     let builder = match Builder::new(workspace.build_dir())? {
         Some(builder) => builder,
         None => {
-            let res = Report {
-                result: ResultType::SystemError,
-                usage: None,
-                message: String::from("The language of the source code is not supported"),
-            };
-            if reporter.send(res).is_err() {
-                return Err(broken_channel());
-            }
-            return Ok(());
+            let res = Report::new(
+                ResultType::SystemError,
+                None,
+                String::from("The language of the source code is not supported"),
+            );
+            return send_report(&reporter, res.into_summary());
         }
     };
+    if let Err(message) = builder.check_toolchain()? {
+        let res = Report::new(ResultType::SystemError, None, message);
+        return send_report(&reporter, res.into_summary());
+    }
     let build_result = builder.build().await?;
+    cancellation.check()?;
     if !build_result.success {
-        let res = Report {
-            result: ResultType::CompileError,
-            usage: None,
-            message: String::from_utf8(build_result.stderr).unwrap_or_else(|_e| {
+        let res = Report::new(
+            ResultType::CompileError,
+            None,
+            String::from_utf8(build_result.stderr).unwrap_or_else(|_e| {
                 String::from("Stderr of building process is not an valid utf8 string")
             }),
-        };
-        if reporter.send(res).is_err() {
-            return Err(broken_channel());
-        }
-        return Ok(());
+        );
+        return send_report(&reporter, res.into_summary());
     }
     log::debug!(
         "Building source code in {} is finished",
         workspace.build_dir().display()
     );
 
-    log::debug!("Create runtime folder {}", workspace.build_dir().display());
+    let problem_dir = workspace.problem_dir();
+    let checker = match problem_dir.config().problem_type {
+        ProblemType::Normal => {
+            let config = problem_dir.config();
+            let mode = match config.compare_mode.unwrap_or_default() {
+                CompareMode::Line => comparer::Mode::Line,
+                CompareMode::Token => comparer::Mode::Token,
+                CompareMode::Float => comparer::Mode::Float {
+                    absolute_error: config.absolute_error.unwrap_or(DEFAULT_FLOAT_ERROR),
+                    relative_error: config.relative_error.unwrap_or(DEFAULT_FLOAT_ERROR),
+                },
+            };
+            Checker::Comparer(
+                Comparer::new(
+                    config.ignore_white_space_at_eol.unwrap_or(true),
+                    config.ignore_empty_line_at_eof.unwrap_or(true),
+                )
+                .mode(mode),
+            )
+        }
+        ProblemType::SpecialJudge => {
+            match build_extern_program(&workspace, "special judge").await? {
+                Ok(spj_dir) => Checker::SpecialJudge(spj_dir),
+                Err(res) => return send_report(&reporter, res.into_summary()),
+            }
+        }
+        ProblemType::Interactive => match build_extern_program(&workspace, "interactor").await? {
+            Ok(interactor_dir) => Checker::Interactor(interactor_dir),
+            Err(res) => return send_report(&reporter, res.into_summary()),
+        },
+    };
+
+    log::debug!(
+        "Create runtime folder {}",
+        workspace.runtime_dir().display()
+    );
 
     // hold runtime folder
-    let runtime_holder = RuntimeHolder::new(
+    let _runtime_holder = RuntimeHolder::new(
         workspace.runtime_dir(),
         workspace.config().runner.rootfs.as_ref(),
     )?;
+    copy_target(workspace.build_dir(), workspace.runtime_dir()).await?;
 
-    log::debug!(
-        "Start move compiled file to runtime directory {}",
-        workspace.runtime_dir().display()
-    );
-    for file in workspace.build_dir().target_dir().read_dir()? {
-        let src = file?.path();
-        let dst = workspace.runtime_dir().join(
-            src.strip_prefix(workspace.build_dir().target_dir())
-                .unwrap(),
-        );
-        // skip directory
-        if src.is_dir() {
-            continue;
+    // the special judge and the interactor run in their own runtime folder
+    let _checker_holder = match &checker {
+        Checker::SpecialJudge(extern_dir) | Checker::Interactor(extern_dir) => {
+            let holder = RuntimeHolder::new(workspace.checker_dir(), None)?;
+            copy_target(extern_dir, workspace.checker_dir()).await?;
+            Some(holder)
         }
-        fs::copy(src, dst).await?;
-    }
-    log::debug!(
-        "All compiled file has been moved to runtime directory {}",
-        workspace.runtime_dir().display()
-    );
+        Checker::Comparer(_) => None,
+    };
 
     log::debug!("Start run program in {}", workspace.runtime_dir().display());
-    let problem_dir = workspace.problem_dir();
-    match problem_dir.config().problem_type {
-        ProblemType::Normal => {
-            for case in workspace.problem_dir().cases() {
-                let runtime_dir = workspace.runtime_dir();
-                if runtime_dir.input_file().exists() {
-                    fs::remove_file(runtime_dir.input_file()).await?;
-                }
-                if runtime_dir.output_file().exists() {
-                    fs::remove_file(runtime_dir.output_file()).await?;
-                }
-                let runner_config = &workspace.config().runner;
-                log::debug!("Symlink the input file {}", case.input_file().display());
-                unix_fs::symlink(case.input_file(), runtime_dir.input_file())?;
-                log::debug!("Run the program in {}", runtime_dir.display());
-                let mut child = Runner::new(&runtime_dir, runner_config).await?
-                    .stdin(File::open(runtime_dir.input_file())?)
-                    .stdout(File::create(runtime_dir.output_file())?)
-                    .stderr(Stdio::piped())
-                    .spawn()?;
-                log::debug!(
-                    "Wait the process and get the result {}",
-                    runtime_dir.display()
-                );
-                // java or any other jvm based language luckily have the bonus
-                let cpu_time_limit = problem_dir
-                    .config()
-                    .limit.cpu_time
-                    .mul_f64(
-                        runner_config.time_limit_ratio.unwrap_or(1.0)
-                    );
-                let real_time_limit = problem_dir
-                    .config()
-                    .limit.real_time
-                    .mul_f64(
-                        runner_config.time_limit_ratio.unwrap_or(1.0)
-                    );
-                let start_time = Instant::now();
-                let exit_status = child.timeout(real_time_limit)?;
-                let resource_usage = {
-                    let real_time = start_time.elapsed();
-                    let (memory, cpu_time) = child.get_resource_usage()?;
-                    Resource {
-                        memory,
-                        cpu_time,
-                        real_time,
-                    }
-                };
+    let limit = Limit::new(&problem_dir.config().limit, &workspace.config().runner);
+    // the configuration of the workspace overrides the one of the problem
+    let run_all_cases = workspace
+        .config()
+        .run_all_cases
+        .or(problem_dir.config().run_all_cases)
+        .unwrap_or(false);
+    if let Some(subtasks) = problem_dir.config().subtasks.as_ref() {
+        return judge_subtasks(
+            &workspace,
+            subtasks,
+            &limit,
+            &checker,
+            run_all_cases,
+            &reporter,
+            cancellation,
+        )
+        .await;
+    }
 
-                let stderr = child.stderr();
-                log::debug!("Generate the process report of {}, {:?}", runtime_dir.display(), &resource_usage);
+    let mut total_result = ResultType::Accepted;
+    let mut max_usage: Option<Resource> = None;
+    let mut accepted = 0;
+    let mut total = 0;
+    for (index, case) in problem_dir.cases().enumerate() {
+        let mut res = run_case(&workspace, &case, &limit, &checker, cancellation).await?;
+        res.case = Some(index);
+        let result_type = res.result.clone();
+        if let Some(usage) = res.usage {
+            max_usage = Some(match max_usage {
+                Some(max_usage) => max_usage.max(&usage),
+                None => usage,
+            });
+        }
+        send_report(&reporter, res)?;
+        total += 1;
+        if result_type == ResultType::Accepted {
+            accepted += 1;
+        } else {
+            if total_result == ResultType::Accepted {
+                total_result = result_type;
+            }
+            if !run_all_cases {
+                break;
+            }
+        }
+    }
+    let res = Report::new(
+        total_result,
+        max_usage,
+        format!("{} of {} cases are accepted", accepted, total),
+    );
+    send_report(&reporter, res.into_summary())
+}
 
-                let mut message = String::new();
+/// Judge the cases of the problem subtask by subtask.
+///
+/// A report with the score is sent after each subtask is finished
+/// and a summary report with the total score is sent at last.
+/// Cases shared by several subtasks are judged only once.
+/// Failed subtasks still judge all of their cases if `run_all_cases` is set.
+async fn judge_subtasks(
+    workspace: &Workspace,
+    subtasks: &[Subtask],
+    limit: &Limit,
+    checker: &Checker,
+    run_all_cases: bool,
+    reporter: &mpsc::UnboundedSender<Report>,
+    cancellation: &Cancellation,
+) -> io::Result<()> {
+    let mut case_results: HashMap<usize, ResultType> = HashMap::new();
+    let mut subtask_results: Vec<ResultType> = Vec::with_capacity(subtasks.len());
+    let mut total_result = ResultType::Accepted;
+    let mut total_score = 0.0;
+    let mut full_score = 0.0;
 
-                let mem_limit = (problem_dir.config().limit.memory as f64 * runner_config.mem_limit_ratio.unwrap_or(1.0)) as usize;
+    for (index, subtask) in subtasks.iter().enumerate() {
+        let scoring = subtask.scoring.unwrap_or_default();
+        let mut result_type = ResultType::Accepted;
+        let mut message = String::new();
+        let mut accepted = 0;
 
-                let result_type = if resource_usage.memory >= mem_limit {
-                    ResultType::MemoryLimitExceeded
-                } else if resource_usage.cpu_time > cpu_time_limit ||
-                    resource_usage.real_time > real_time_limit
-                {
-                    ResultType::TimeLimitExceeded
-                } else if !exit_status.success() {
-                    let mut buffer = vec![0; 1024];
-                    if let Some(out) = stderr {
-                        out.read(buffer.as_mut()); 
-                        message = String::from_utf8_lossy(buffer.as_slice()).to_string();
+        let failed_dependency = subtask
+            .dependencies
+            .iter()
+            .flatten()
+            .find(|&&dependency| subtask_results[dependency] != ResultType::Accepted);
+        if let Some(&dependency) = failed_dependency {
+            result_type = subtask_results[dependency].clone();
+            message = format!("Skipped because the subtask {} is failed", dependency);
+        }
+        if failed_dependency.is_none() || run_all_cases {
+            for case_index in subtask.cases.clone() {
+                let case_result = match case_results.get(&case_index) {
+                    Some(case_result) => case_result.clone(),
+                    None => {
+                        let case = workspace.problem_dir().case(case_index).ok_or_else(|| {
+                            io::Error::new(
+                                io::ErrorKind::NotFound,
+                                format!(
+                                    "The case {} of the subtask {} is missing",
+                                    case_index, index
+                                ),
+                            )
+                        })?;
+                        let mut res =
+                            run_case(workspace, &case, limit, checker, cancellation).await?;
+                        res.case = Some(case_index);
+                        res.subtask = Some(index);
+                        let case_result = res.result.clone();
+                        send_report(reporter, res)?;
+                        case_results.insert(case_index, case_result.clone());
+                        case_result
                     }
-                    ResultType::RuntimeError
-                } else if Comparer::new(
-                    problem_dir
-                        .config()
-                        .ignore_white_space_at_eol
-                        .unwrap_or(true),
-                    problem_dir
-                        .config()
-                        .ignore_empty_line_at_eof
-                        .unwrap_or(true),
-                )
-                .compare_files(runtime_dir.output_file(), case.answer_file())
-                .await?
-                {
-                    ResultType::Accepted
-                } else {
-                    ResultType::WrongAnswer
                 };
-
-                let res = Report {
-                    result: result_type.clone(),
-                    usage: Some(resource_usage),
-                    message,
-                };
-                if reporter.send(res).is_err() {
-                    return Err(broken_channel());
-                }
-                if result_type != ResultType::Accepted {
-                    break;
+                if case_result == ResultType::Accepted {
+                    accepted += 1;
+                } else {
+                    if result_type == ResultType::Accepted {
+                        result_type = case_result;
+                    }
+                    // the rest cases can not change the score of the subtask
+                    if scoring == Scoring::Min && !run_all_cases {
+                        break;
+                    }
                 }
             }
         }
-        ProblemType::SpecialJudge => {
-            let spj_dir = BuildDir::from_path(workspace.problem_dir().extern_program())?;
-            let spj_builder = Builder::new(&spj_dir)?;
-            let spj_builder = match spj_builder {
-                Some(spj_builder) => spj_builder,
-                None => {
-                    let res = Report {
-                        result: ResultType::SystemError,
-                        usage: None,
-                        message: String::from("The special judge of the problem is missing."),
-                    };
-                    if reporter.send(res).is_err() {
-                        return Err(broken_channel());
+
+        let score = match scoring {
+            _ if failed_dependency.is_some() => 0.0,
+            Scoring::Min if result_type == ResultType::Accepted => subtask.score,
+            Scoring::Min => 0.0,
+            Scoring::Sum => subtask.score * accepted as f64 / subtask.cases.len() as f64,
+        };
+        if total_result == ResultType::Accepted {
+            total_result = result_type.clone();
+        }
+        total_score += score;
+        full_score += subtask.score;
+        subtask_results.push(result_type.clone());
+
+        let mut res = Report::new(result_type, None, message);
+        res.score = Some(Score {
+            subtask: Some(index),
+            score,
+            full_score: subtask.score,
+        });
+        send_report(reporter, res)?;
+    }
+
+    let mut res = Report::new(total_result, None, String::new());
+    res.score = Some(Score {
+        subtask: None,
+        score: total_score,
+        full_score,
+    });
+    send_report(reporter, res.into_summary())
+}
+
+/// Judge a single case with the checker of the problem.
+///
+/// The report of the case is dropped if the task is cancelled meanwhile,
+/// since the program may be killed by the cancellation.
+async fn run_case(
+    workspace: &Workspace,
+    case: &Case,
+    limit: &Limit,
+    checker: &Checker,
+    cancellation: &Cancellation,
+) -> io::Result<Report> {
+    cancellation.check()?;
+    let res = match checker {
+        Checker::Interactor(interactor) => {
+            judge_interactive_case(workspace, interactor, case, limit, cancellation).await?
+        }
+        checker => judge_case(workspace, case, limit, checker, cancellation).await?,
+    };
+    cancellation.check()?;
+    Ok(res)
+}
+
+/// Default max size of the output file.
+const DEFAULT_OUTPUT_LIMIT: usize = 256 * 1024 * 1024;
+
+/// Resource limit of the program after applying the ratios of the runner.
+struct Limit {
+    cpu_time: Duration,
+    real_time: Duration,
+    memory: usize,
+    output: usize,
+    processes: Option<usize>,
+}
+
+impl Limit {
+    fn new(limit: &ResourceLimit, runner_config: &RunnerConfig) -> Limit {
+        // java or any other jvm based language luckily have the bonus
+        let time_limit_ratio = runner_config.time_limit_ratio.unwrap_or(1.0);
+        let mem_limit_ratio = runner_config.mem_limit_ratio.unwrap_or(1.0);
+        Limit {
+            cpu_time: limit.cpu_time.mul_f64(time_limit_ratio),
+            real_time: limit.real_time.mul_f64(time_limit_ratio),
+            memory: (limit.memory as f64 * mem_limit_ratio) as usize,
+            output: limit.output.unwrap_or(DEFAULT_OUTPUT_LIMIT),
+            processes: limit.processes,
+        }
+    }
+
+    /// Generate the cgroup of the program which enforces
+    /// the memory limit and the process limit in the kernel.
+    ///
+    /// The smaller one of the memory limit and `memory_limit` of the config is used,
+    /// and `memsw_limit` of the config is kept as the allowance of swap.
+    fn cgroup(&self, runner_config: &RunnerConfig) -> cgroup::Builder {
+        let cgroups_config = runner_config.cgroups.clone().unwrap_or_default();
+        let memory = cgroups_config
+            .memory_limit
+            .map_or(self.memory, |limit| limit.min(self.memory));
+        let memsw = cgroups_config
+            .memsw_limit
+            .map_or(memory, |limit| limit.max(memory));
+        let builder = cgroups_config
+            .builder()
+            .memory_limit(memory)
+            .memsw_limit(memsw);
+        match self.processes {
+            Some(processes) => builder.pids_controller(true).pids_limit(processes),
+            None => builder,
+        }
+    }
+
+    /// Check whether the resource usage exceeds the limit.
+    fn check(&self, usage: &Resource, oom_killed: bool) -> Option<ResultType> {
+        if oom_killed || usage.memory >= self.memory {
+            Some(ResultType::MemoryLimitExceeded)
+        } else if usage.cpu_time > self.cpu_time || usage.real_time > self.real_time {
+            Some(ResultType::TimeLimitExceeded)
+        } else {
+            None
+        }
+    }
+}
+
+/// Judge a case of a normal problem or a special judge problem.
+async fn judge_case(
+    workspace: &Workspace,
+    case: &Case,
+    limit: &Limit,
+    checker: &Checker,
+    cancellation: &Cancellation,
+) -> io::Result<Report> {
+    let runtime_dir = workspace.runtime_dir();
+    if runtime_dir.input_file().exists() {
+        fs::remove_file(runtime_dir.input_file()).await?;
+    }
+    if runtime_dir.output_file().exists() {
+        fs::remove_file(runtime_dir.output_file()).await?;
+    }
+    log::debug!("Symlink the input file {}", case.input_file().display());
+    unix_fs::symlink(case.input_file(), runtime_dir.input_file())?;
+    log::debug!("Run the program in {}", runtime_dir.display());
+    let runner_config = &workspace.config().runner;
+    let mut child = Runner::with_cgroup(runtime_dir, runner_config, limit.cgroup(runner_config))
+        .await?
+        .stdin(File::open(runtime_dir.input_file())?)
+        .stdout(File::create(runtime_dir.output_file())?)
+        .stderr(Stdio::piped())
+        .output_limit(limit.output)
+        .spawn()?;
+    let _watch = cancellation.watch(&child);
+    log::debug!(
+        "Wait the process and get the result {}",
+        runtime_dir.display()
+    );
+    let (exit_status, resource_usage) = wait_program(&mut child, Instant::now(), limit.real_time)?;
+    log::debug!(
+        "Generate the process report of {}, {:?}",
+        runtime_dir.display(),
+        &resource_usage
+    );
+
+    let mut message = String::new();
+    let result_type = if let Some((result_type, error)) =
+        exit_result(&mut child, exit_status, &resource_usage, limit)?
+    {
+        message = error;
+        result_type
+    } else {
+        let difference = match checker {
+            Checker::Comparer(comparer) => comparer
+                .diff_files(runtime_dir.output_file(), case.answer_file())
+                .await?
+                .map(|mismatch| {
+                    let config = workspace.problem_dir().config();
+                    if config.hide_mismatch.unwrap_or(false) {
+                        String::new()
+                    } else {
+                        mismatch.to_string()
+                    }
+                }),
+            Checker::SpecialJudge(spj_dir) => {
+                match run_special_judge(workspace, spj_dir, case, limit, cancellation).await? {
+                    Ok(true) => None,
+                    Ok(false) => Some(String::new()),
+                    Err(message) => {
+                        return Ok(Report::new(
+                            ResultType::SystemError,
+                            Some(resource_usage),
+                            message,
+                        ))
                     }
-                    return Ok(());
-                }
-            };
-            let spj_build_result = spj_builder.build().await?;
-            if !spj_build_result.success {
-                let res = Report {
-                    result: ResultType::SystemError,
-                    usage: None,
-                    message: String::from("Failed to build the special judge of the problem."),
-                };
-                if reporter.send(res).is_err() {
-                    return Err(broken_channel());
                 }
-                return Ok(());
             }
+            Checker::Interactor(_) => {
+                unreachable!("Interactive problem is judged by the interactor")
+            }
+        };
+        match difference {
+            None => ResultType::Accepted,
+            Some(difference) => {
+                message = difference;
+                ResultType::WrongAnswer
+            }
+        }
+    };
 
-            for case in workspace.problem_dir().cases() {
-                let runtime_dir = workspace.runtime_dir();
-                if runtime_dir.input_file().exists() {
-                    fs::remove_file(runtime_dir.input_file()).await?;
-                }
-                if runtime_dir.output_file().exists() {
-                    fs::remove_file(runtime_dir.output_file()).await?;
-                }
-                let runner_config = &workspace.config().runner;
-                log::debug!("Symlink the input file {}", case.input_file().display());
-                unix_fs::symlink(case.input_file(), runtime_dir.input_file())?;
-                log::debug!("Run the program in {}", runtime_dir.display());
-                let mut child = Runner::new(&runtime_dir, runner_config).await?
-                    .stdin(File::open(runtime_dir.input_file())?)
-                    .stdout(File::create(runtime_dir.output_file())?)
-                    .stderr(Stdio::piped())
-                    .spawn()?;
-                log::debug!(
-                    "Wait the process and get the result {}",
-                    runtime_dir.display()
-                );
-                // java or any other jvm based language luckily have the bonus
-                let cpu_time_limit = problem_dir
-                    .config()
-                    .limit.cpu_time
-                    .mul_f64(
-                        runner_config.time_limit_ratio.unwrap_or(1.0)
-                    );
-                let real_time_limit = problem_dir
-                    .config()
-                    .limit.real_time
-                    .mul_f64(
-                        runner_config.time_limit_ratio.unwrap_or(1.0)
-                    );
-                let start_time = Instant::now();
-                let exit_status = child.timeout(real_time_limit)?;
-                let resource_usage = {
-                    let real_time = start_time.elapsed();
-                    let (memory, cpu_time) = child.get_resource_usage()?;
-                    Resource {
-                        memory,
-                        cpu_time,
-                        real_time,
-                    }
-                };
-                log::debug!("Generate the process report of {}", runtime_dir.display());
-                dbg!(&resource_usage);
-
-                let mem_limit = (problem_dir.config().limit.memory as f64 * runner_config.mem_limit_ratio.unwrap_or(1.0)) as usize;
-
-                let result_type = if resource_usage.memory >= mem_limit {
-                    ResultType::MemoryLimitExceeded
-                } else if resource_usage.cpu_time > cpu_time_limit
-                    || resource_usage.real_time > real_time_limit
-                {
-                    ResultType::TimeLimitExceeded
-                } else if !exit_status.success() {
-                    ResultType::RuntimeError
-                } else if Command::new(spj_dir.executable_file())
-                    .arg(case.input_file())
-                    .arg(runtime_dir.output_file())
-                    .arg(case.answer_file())
-                    .spawn()?
-                    .wait()?
-                    .success()
-                {
-                    ResultType::Accepted
-                } else {
-                    ResultType::WrongAnswer
-                };
+    Ok(Report::new(result_type, Some(resource_usage), message))
+}
 
-                let res = Report {
-                    result: result_type.clone(),
-                    usage: Some(resource_usage),
-                    message: String::new(),
-                };
-                if reporter.send(res).is_err() {
-                    return Err(broken_channel());
-                }
-                if result_type != ResultType::Accepted {
-                    break
-                }
-            }
+/// Check the exit status and the resource usage of the program.
+///
+/// Return `None` if the program exits normally within the limit.
+fn exit_result(
+    child: &mut Program,
+    exit_status: ExitStatus,
+    usage: &Resource,
+    limit: &Limit,
+) -> io::Result<Option<(ResultType, String)>> {
+    let res = if let Some(result_type) = limit.check(usage, child.oom_killed()?) {
+        (result_type, String::new())
+    } else if exit_status.signal() == Some(nix::libc::SIGXFSZ) {
+        (ResultType::OutputLimitExceeded, String::new())
+    } else if exit_status.signal() == Some(nix::libc::SIGSYS) {
+        (ResultType::RestrictedFunction, String::new())
+    } else if !exit_status.success() {
+        (ResultType::RuntimeError, runtime_error_message(child)?)
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(res))
+}
+
+/// Exit status and resource usage of a program run by `run_program`.
+#[derive(Debug)]
+pub struct Execution {
+    pub exit_status: ExitStatus,
+    pub usage: Resource,
+    /// Result of the limit and the exit status, `None` if the program exits
+    /// normally within the limit.
+    pub result: Option<ResultType>,
+    /// Beginning of the stderr if the program exits abnormally.
+    pub message: String,
+}
+
+/// Run a program in the runtime directory with the limit in the same sandbox
+/// as the programs of the cases.
+pub async fn run_program(
+    runtime_dir: &RuntimeDir,
+    runner_config: &RunnerConfig,
+    limit: &ResourceLimit,
+    stdin: Stdio,
+    stdout: Stdio,
+) -> io::Result<Execution> {
+    let limit = Limit::new(limit, runner_config);
+    log::debug!("Run the program in {}", runtime_dir.display());
+    let mut child = Runner::with_cgroup(runtime_dir, runner_config, limit.cgroup(runner_config))
+        .await?
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .output_limit(limit.output)
+        .spawn()?;
+    let (exit_status, usage) = wait_program(&mut child, Instant::now(), limit.real_time)?;
+    let (result, message) = match exit_result(&mut child, exit_status, &usage, &limit)? {
+        Some((result, message)) => (Some(result), message),
+        None => (None, String::new()),
+    };
+    Ok(Execution {
+        exit_status,
+        usage,
+        result,
+        message,
+    })
+}
+
+/// Judge a case of an interactive problem.
+///
+/// The stdout of the program is connected to the stdin of the interactor
+/// and the stdout of the interactor is connected to the stdin of the program.
+/// The interactor is executed as `interactor input answer` in its own runtime directory,
+/// or by the runner command of its language with `input answer` after the arguments.
+async fn judge_interactive_case(
+    workspace: &Workspace,
+    interactor: &BuildDir,
+    case: &Case,
+    limit: &Limit,
+    cancellation: &Cancellation,
+) -> io::Result<Report> {
+    let runtime_dir = workspace.runtime_dir();
+    let interactor_dir = workspace.checker_dir();
+    fs::copy(case.input_file(), interactor_dir.input_file()).await?;
+    fs::copy(case.answer_file(), interactor_dir.answer_file()).await?;
+
+    let interactor_config = extern_runner_config(interactor, &["input", "answer"])?;
+    let runner_config = &workspace.config().runner;
+    // the interactor has the limits of the program,
+    // but must not wait for a cpuset which may be held by the program
+    let interactor_cgroup = limit.cgroup(runner_config).cpuset_controller(false, 0);
+
+    let (program_stdin, interactor_stdout) = pipe()?;
+    let (interactor_stdin, program_stdout) = pipe()?;
+    log::debug!("Run the interactor in {}", interactor_dir.display());
+    let mut interactor = Runner::with_cgroup(interactor_dir, &interactor_config, interactor_cgroup)
+        .await?
+        .stdin(interactor_stdin)
+        .stdout(interactor_stdout)
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let _interactor_watch = cancellation.watch(&interactor);
+    // the interactor is killed and reaped when it is dropped on the errors below
+    log::debug!("Run the program in {}", runtime_dir.display());
+    let mut child = Runner::with_cgroup(runtime_dir, runner_config, limit.cgroup(runner_config))
+        .await?
+        .stdin(program_stdin)
+        .stdout(program_stdout)
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let _watch = cancellation.watch(&child);
+
+    // both of them are killed at the same deadline
+    let start_time = Instant::now();
+    let (exit_status, resource_usage) = wait_program(&mut child, start_time, limit.real_time)?;
+    let (interactor_status, interactor_usage) =
+        wait_program(&mut interactor, start_time, limit.real_time)?;
+    log::debug!(
+        "Generate the process report of {}, {:?}, interactor {:?}",
+        runtime_dir.display(),
+        &resource_usage,
+        &interactor_usage
+    );
+
+    // the program may be killed by SIGPIPE when the interactor exits early
+    let broken_pipe = exit_status.signal() == Some(nix::libc::SIGPIPE);
+    let mut message = String::new();
+    let result_type = if let Some(result_type) = limit.check(&resource_usage, child.oom_killed()?) {
+        result_type
+    } else if exit_status.signal() == Some(nix::libc::SIGSYS) {
+        ResultType::RestrictedFunction
+    } else if !exit_status.success() && !broken_pipe {
+        message = runtime_error_message(&mut child)?;
+        ResultType::RuntimeError
+    } else if interactor_status.signal().is_some() {
+        message = String::from("The interactor exited abnormally");
+        ResultType::SystemError
+    } else if !interactor_status.success() {
+        message = read_stderr(&mut interactor)?;
+        ResultType::WrongAnswer
+    } else if broken_pipe {
+        message = runtime_error_message(&mut child)?;
+        ResultType::RuntimeError
+    } else {
+        ResultType::Accepted
+    };
+
+    let mut res = Report::new(result_type, Some(resource_usage), message);
+    res.interactor_usage = Some(interactor_usage);
+    Ok(res)
+}
+
+/// Run the special judge of the problem on the output of the program.
+///
+/// The special judge is executed as `spj input output answer` in its own runtime directory,
+/// or by the runner command of its language with `input output answer` after the arguments,
+/// and has the limits of the program. Return whether the output is accepted,
+/// or a message if the special judge exits abnormally.
+async fn run_special_judge(
+    workspace: &Workspace,
+    spj: &BuildDir,
+    case: &Case,
+    limit: &Limit,
+    cancellation: &Cancellation,
+) -> io::Result<Result<bool, String>> {
+    let spj_dir = workspace.checker_dir();
+    fs::copy(case.input_file(), spj_dir.input_file()).await?;
+    fs::copy(workspace.runtime_dir().output_file(), spj_dir.output_file()).await?;
+    fs::copy(case.answer_file(), spj_dir.answer_file()).await?;
+
+    let spj_config = extern_runner_config(spj, &["input", "output", "answer"])?;
+    let runner_config = &workspace.config().runner;
+    // the cpuset may still be held by the program
+    let spj_cgroup = limit.cgroup(runner_config).cpuset_controller(false, 0);
+    log::debug!("Run the special judge in {}", spj_dir.display());
+    let mut child = Runner::with_cgroup(spj_dir, &spj_config, spj_cgroup)
+        .await?
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output_limit(limit.output)
+        .spawn()?;
+    let _watch = cancellation.watch(&child);
+    let (exit_status, usage) = wait_program(&mut child, Instant::now(), limit.real_time)?;
+    if limit.check(&usage, child.oom_killed()?).is_some() || exit_status.signal().is_some() {
+        return Ok(Err(String::from("The special judge exited abnormally")));
+    }
+    Ok(Ok(exit_status.success()))
+}
+
+/// Runner config of the extern program, which is its executable file or the runner command
+/// of its language with `args` after the arguments.
+fn extern_runner_config(extern_dir: &BuildDir, args: &[&str]) -> io::Result<RunnerConfig> {
+    let mut config = match extern_dir.language() {
+        Some(language) => language.runner_defaults()?,
+        None => RunnerConfig::default(),
+    };
+    let mut extern_args = config.args.take().unwrap_or_default();
+    extern_args.extend(args.iter().map(|arg| String::from(*arg)));
+    config.args = Some(extern_args);
+    Ok(config)
+}
+
+/// Build the extern program of the problem.
+///
+/// Return a report if the extern program can not be built.
+async fn build_extern_program(
+    workspace: &Workspace,
+    name: &str,
+) -> io::Result<Result<BuildDir, Report>> {
+    let extern_dir = BuildDir::from_path(workspace.problem_dir().extern_program())?;
+    let extern_builder = match Builder::new(&extern_dir)? {
+        Some(extern_builder) => extern_builder,
+        None => {
+            return Ok(Err(Report::new(
+                ResultType::SystemError,
+                None,
+                format!("The {} of the problem is missing.", name),
+            )))
         }
-        ProblemType::Interactive => unimplemented!("TODO: Interactive support"),
+    };
+    if let Err(message) = extern_builder.check_toolchain()? {
+        return Ok(Err(Report::new(ResultType::SystemError, None, message)));
+    }
+    if !extern_builder.build().await?.success {
+        return Ok(Err(Report::new(
+            ResultType::SystemError,
+            None,
+            format!("Failed to build the {} of the problem.", name),
+        )));
+    }
+    Ok(Ok(extern_dir))
+}
+
+/// Copy the compiled files to the runtime directory.
+async fn copy_target(build_dir: &BuildDir, runtime_dir: &RuntimeDir) -> io::Result<()> {
+    log::debug!(
+        "Start move compiled file to runtime directory {}",
+        runtime_dir.display()
+    );
+    for file in build_dir.target_dir().read_dir()? {
+        let src = file?.path();
+        let dst = runtime_dir.join(src.strip_prefix(build_dir.target_dir()).unwrap());
+        // skip directory
+        if src.is_dir() {
+            continue;
+        }
+        fs::copy(src, dst).await?;
+    }
+    log::debug!(
+        "All compiled file has been moved to runtime directory {}",
+        runtime_dir.display()
+    );
+    Ok(())
+}
+
+/// Wait for the program and get the resource usage of it.
+///
+/// The program is killed if it does not exit in `real_time_limit` after `start_time`.
+fn wait_program(
+    child: &mut Program,
+    start_time: Instant,
+    real_time_limit: Duration,
+) -> io::Result<(ExitStatus, Resource)> {
+    let timeout = real_time_limit
+        .checked_sub(start_time.elapsed())
+        .unwrap_or_default();
+    let exit_status = child.timeout(timeout)?;
+    let real_time = start_time.elapsed();
+    let (memory, cpu_time) = child.get_resource_usage()?;
+    let resource_usage = Resource {
+        memory,
+        cpu_time,
+        real_time,
+    };
+    Ok((exit_status, resource_usage))
+}
+
+/// Explain why the program exited abnormally.
+fn runtime_error_message(child: &mut Program) -> io::Result<String> {
+    if child.pids_limit_hit()? {
+        Ok(String::from("The number of processes exceeds the limit"))
+    } else {
+        read_stderr(child)
+    }
+}
+
+/// Read at most 1024 bytes from the stderr of the program.
+fn read_stderr(child: &mut Program) -> io::Result<String> {
+    let mut buffer = vec![0; 1024];
+    let mut len = 0;
+    if let Some(stderr) = child.stderr() {
+        len = stderr.read(buffer.as_mut())?;
+    }
+    Ok(String::from_utf8_lossy(&buffer[..len]).to_string())
+}
+
+/// Create a pipe whose file descriptors are closed on exec.
+fn pipe() -> io::Result<(File, File)> {
+    let (read_end, write_end) =
+        nix::unistd::pipe2(nix::fcntl::OFlag::O_CLOEXEC).map_err(io::Error::other)?;
+    unsafe { Ok((File::from_raw_fd(read_end), File::from_raw_fd(write_end))) }
+}
+
+fn send_report(reporter: &mpsc::UnboundedSender<Report>, report: Report) -> io::Result<()> {
+    if reporter.send(report).is_err() {
+        return Err(broken_channel());
     }
-    // depress unsed variable warning
-    drop(runtime_holder);
     Ok(())
 }
 
@@ -1,29 +1,184 @@
+use std::env;
 use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::RwLock;
 
+use lazy_static::lazy_static;
+
+use crate::builder::BUILD_PATH;
+use crate::workspace::RunnerConfig;
+
+/// Language directory of the source tree, which is searched after the installed ones.
+const SOURCE_LANG_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/lang");
+
+lazy_static! {
+    static ref SEARCH_PATHS: RwLock<Vec<PathBuf>> = RwLock::new(
+        [
+            "/etc/ana/lang",
+            "/usr/local/share/ana/lang",
+            "/usr/share/ana/lang",
+            SOURCE_LANG_DIR,
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect()
+    );
+}
+
+/// Search the languages in `paths` in order instead of the default directories.
+///
+/// A language in an earlier directory hides the one with the same name in a later one.
+pub fn set_search_paths(paths: Vec<PathBuf>) {
+    *SEARCH_PATHS.write().unwrap() = paths;
+}
+
+/// Directories where the languages are searched.
+pub fn search_paths() -> Vec<PathBuf> {
+    SEARCH_PATHS.read().unwrap().clone()
+}
+
+/// A language directory named `{extension}.{toolchain}` with a `build.sh`, a `builder.toml`
+/// and a `runner.toml`.
+///
+/// The language named `{extension}.default` is used for the source files with the extension,
+/// which is usually a symlink to another language. The `builder.toml` declares the commands
+/// used by the build script in `tools`.
+#[derive(Debug)]
 pub struct Language {
     language_path: PathBuf,
 }
 
 impl Language {
-    const LANG_DIR: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/lang");
-
     pub fn new<S: AsRef<OsStr>>(lang: S) -> Option<Language> {
-        let language_path = Path::new(Self::LANG_DIR).join(lang.as_ref());
-        if language_path.exists() {
-            Some(Language { language_path })
-        } else {
-            None
+        Language::find_in(lang, &search_paths())
+    }
+
+    /// Find the language in `paths` instead of the search paths.
+    pub fn find_in<S: AsRef<OsStr>>(lang: S, paths: &[PathBuf]) -> Option<Language> {
+        // the language must be a directory in the search paths
+        let name = lang.as_ref().to_string_lossy();
+        if name.is_empty() || name.starts_with('.') || name.contains('/') {
+            return None;
         }
+        paths
+            .iter()
+            .map(|dir| dir.join(lang.as_ref()))
+            .find(|language_path| language_path.is_dir())
+            .map(|language_path| Language { language_path })
     }
 
     pub fn from_ext<S: AsRef<OsStr>>(ext: S) -> Option<Language> {
-        let mut language_path = Path::new(Self::LANG_DIR).join(ext.as_ref());
-        if language_path.set_extension("default") && language_path.exists() {
-            Some(Language { language_path })
-        } else {
-            None
+        let mut lang = ext.as_ref().to_owned();
+        lang.push(".default");
+        Language::new(lang)
+    }
+
+    /// All the languages in the search paths except the default aliases, sorted by name.
+    pub fn all() -> io::Result<Vec<Language>> {
+        Language::all_in(&search_paths())
+    }
+
+    /// All the languages in `paths` instead of the search paths.
+    pub fn all_in(paths: &[PathBuf]) -> io::Result<Vec<Language>> {
+        let mut languages: Vec<Language> = Vec::new();
+        for dir in paths.iter().filter(|dir| dir.is_dir()) {
+            for entry in dir.read_dir()? {
+                let entry = entry?;
+                let language_path = entry.path();
+                let is_alias = entry.file_type()?.is_symlink()
+                    && language_path.extension() == Some(OsStr::new("default"));
+                let is_hidden = languages
+                    .iter()
+                    .any(|language| language.language_path.file_name() == Some(&entry.file_name()));
+                if language_path.is_dir() && !is_alias && !is_hidden {
+                    languages.push(Language { language_path });
+                }
+            }
         }
+        languages.sort_by_key(|language| language.name());
+        Ok(languages)
+    }
+
+    /// Name of the language such as `c.gcc`.
+    pub fn name(&self) -> String {
+        self.language_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Extension of the source files such as `c`.
+    pub fn extension(&self) -> String {
+        let name = self.name();
+        match name.split_once('.') {
+            Some((extension, _)) => extension.to_string(),
+            None => name,
+        }
+    }
+
+    /// Whether the language is used for the source files with its extension by default.
+    pub fn is_default(&self) -> bool {
+        match Language::from_ext(self.extension()) {
+            Some(language) => {
+                language.language_path.canonicalize().ok() == self.language_path.canonicalize().ok()
+            }
+            None => false,
+        }
+    }
+
+    /// Commands used by the build script, which are declared by `tools` in the `builder.toml`.
+    ///
+    /// The command of the runner is not included since it may be found in the rootfs
+    /// of the program instead of this host.
+    pub fn tools(&self) -> io::Result<Vec<String>> {
+        let tools = match read_config(&self.builder_config())?.get("tools") {
+            Some(tools) => tools.clone(),
+            None => return Ok(Vec::new()),
+        };
+        tools
+            .try_into()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Commands of the build script which are not found on this host.
+    pub fn missing_tools(&self) -> io::Result<Vec<String>> {
+        Ok(self
+            .tools()?
+            .into_iter()
+            .filter(|tool| !is_available(tool))
+            .collect())
+    }
+
+    /// Version of the toolchain, which is the first line printed by `--version`.
+    ///
+    /// The toolchain is the command of the runner if any, such as `java`,
+    /// otherwise the first command of the build script, such as `gcc`.
+    pub fn version(&self) -> Option<String> {
+        let toolchain = match self.runner_command().ok()? {
+            Some(command) => command,
+            None => self.tools().ok()?.into_iter().next()?,
+        };
+        let output = Command::new(toolchain)
+            .arg("--version")
+            .env("PATH", BUILD_PATH)
+            .stdin(Stdio::null())
+            .output()
+            .ok()?;
+        let output = if output.stdout.is_empty() {
+            output.stderr
+        } else {
+            output.stdout
+        };
+        String::from_utf8_lossy(&output)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(String::from)
     }
 
     pub fn build_script(&self) -> PathBuf {
@@ -37,4 +192,52 @@ impl Language {
     pub fn runner_config(&self) -> PathBuf {
         self.language_path.join("runner.toml")
     }
+
+    /// Default runner config of the language, which is empty without a `runner.toml`.
+    ///
+    /// The config of a workspace in the language is merged with it.
+    pub fn runner_defaults(&self) -> io::Result<RunnerConfig> {
+        read_config(&self.runner_config())?
+            .try_into()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Command of the runner config, which runs the program instead of the executable file.
+    fn runner_command(&self) -> io::Result<Option<String>> {
+        Ok(self
+            .runner_defaults()?
+            .command
+            .map(|command| command.to_string_lossy().into_owned()))
+    }
+}
+
+/// Content of a config file of the language, which is an empty table if it is missing.
+fn read_config(path: &Path) -> io::Result<toml::Value> {
+    let config = match fs::read_to_string(path) {
+        Ok(config) => config,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(toml::Value::Table(Default::default()))
+        }
+        Err(e) => return Err(e),
+    };
+    toml::from_str(&config).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Whether the command is a shell builtin or an executable file in the `PATH` of building.
+fn is_available(command: &str) -> bool {
+    const BUILTINS: &[&str] = &["cd", "echo", "exit", "export", "set", "test", "["];
+
+    fn is_executable(path: &Path) -> bool {
+        path.metadata()
+            .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+
+    if BUILTINS.contains(&command) {
+        return true;
+    }
+    if command.contains('/') {
+        return is_executable(Path::new(command));
+    }
+    env::split_paths(BUILD_PATH).any(|dir| is_executable(&dir.join(command)))
 }
@@ -1,9 +1,15 @@
 use std::io;
 use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
 
+use ana::language;
+use ana::process::{self, cgroup};
+use ana::rpc::RpcServer;
+use ana::workspace::ProblemStore;
 use clap::*;
-use env_logger;
-use tokio;
+
+mod command;
 
 #[tokio::main]
 async fn main() -> io::Result<()> {
@@ -43,8 +49,93 @@ async fn main() -> io::Result<()> {
                 .env("ANA_PORT")
                 .default_value("8800"),
         )
+        .arg(
+            Arg::with_name("cgroup_root")
+                .takes_value(true)
+                .value_name("PATH")
+                .long("cgroup-root")
+                .help("The delegated cgroup v2 directory for programs")
+                .env("ANA_CGROUP_ROOT"),
+        )
+        .arg(
+            Arg::with_name("problem_store")
+                .takes_value(true)
+                .value_name("PATH")
+                .long("problem-store")
+                .help("The directory of the cached problems")
+                .env("ANA_PROBLEM_STORE"),
+        )
+        .arg(
+            Arg::with_name("max_judges")
+                .takes_value(true)
+                .value_name("N")
+                .long("max-judges")
+                .help("The max number of the tasks judged at the same time [default: threads]")
+                .env("ANA_MAX_JUDGES"),
+        )
+        .arg(
+            Arg::with_name("queue_size")
+                .takes_value(true)
+                .value_name("N")
+                .long("queue-size")
+                .help("The max number of the tasks waiting for judging")
+                .env("ANA_QUEUE_SIZE")
+                .default_value("1024"),
+        )
+        .arg(
+            Arg::with_name("task_ttl")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .long("task-ttl")
+                .help("The time of keeping the finished tasks")
+                .env("ANA_TASK_TTL")
+                .default_value("600"),
+        )
+        .arg(
+            Arg::with_name("trust_clients")
+                .long("trust-clients")
+                .help("Accept the build scripts, runner config and extern programs of the tasks"),
+        )
+        .arg(
+            Arg::with_name("lang_dir")
+                .takes_value(true)
+                .value_name("PATH")
+                .long("lang-dir")
+                .multiple(true)
+                .number_of_values(1)
+                .use_delimiter(true)
+                .value_delimiter(":")
+                .help("The directories searched for the languages in order")
+                .env("ANA_LANG_PATH"),
+        )
+        .subcommand(command::check::subcommand())
+        .subcommand(command::judge::subcommand())
+        .subcommand(command::languages::subcommand())
+        .subcommand(command::run::subcommand())
         .get_matches();
 
+    if let Some(cgroup_root) = matches.value_of("cgroup_root") {
+        cgroup::delegate(cgroup_root)?;
+    } else if process::rootless() {
+        match cgroup::current() {
+            Ok(cgroup_root) => cgroup::delegate(cgroup_root)?,
+            Err(e) => log::warn!("Failed to use the current cgroup in rootless mode: {}", e),
+        }
+    }
+    if let Some(lang_dirs) = matches.values_of("lang_dir") {
+        language::set_search_paths(lang_dirs.map(PathBuf::from).collect());
+    }
+    let status = match matches.subcommand() {
+        ("check", Some(matches)) => Some(command::check::execute(matches)?),
+        ("judge", Some(matches)) => Some(command::judge::execute(matches).await?),
+        ("languages", Some(matches)) => Some(command::languages::execute(matches)?),
+        ("run", Some(matches)) => Some(command::run::execute(matches).await?),
+        _ => None,
+    };
+    if let Some(status) = status {
+        std::process::exit(status);
+    }
+
     let threads: usize = matches
         .value_of("threads")
         .expect("Failed to get threads argument")
@@ -60,8 +151,35 @@ async fn main() -> io::Result<()> {
         .expect("Failed to get port argument")
         .parse()
         .expect("`port` argument is invalid");
+    let max_judges: usize = match matches.value_of("max_judges") {
+        Some(max_judges) => max_judges
+            .parse()
+            .expect("`max_judges` argument is invalid"),
+        None => threads,
+    };
+    let queue_size: usize = matches
+        .value_of("queue_size")
+        .expect("Failed to get queue_size argument")
+        .parse()
+        .expect("`queue_size` argument is invalid");
+    let task_ttl: u64 = matches
+        .value_of("task_ttl")
+        .expect("Failed to get task_ttl argument")
+        .parse()
+        .expect("`task_ttl` argument is invalid");
 
-    ana::start_rpc_server(address, port, threads);
+    if !command::check::report(&mut io::stderr())? {
+        log::warn!("The isolation features are not sufficient for judging");
+    }
 
-    Ok(())
+    let mut server = RpcServer::new(threads)
+        .queue(max_judges, queue_size)
+        .task_ttl(Duration::from_secs(task_ttl));
+    if let Some(problem_store) = matches.value_of("problem_store") {
+        server = server.problem_store(ProblemStore::new(problem_store)?);
+    }
+    if matches.is_present("trust_clients") {
+        server = server.trust_clients();
+    }
+    server.start(address, port)
 }
@@ -1,3 +1,111 @@
-// TODO: libcap-ng
 //! High-level APIs binding for libcap-ng library.
 mod libcapng;
+use libcapng::*;
+
+use std::ffi::CString;
+use std::io;
+use std::ops::Deref;
+use std::os::unix::process::CommandExt as _;
+use std::process::Command;
+
+/// Capability wrapper.
+#[derive(Clone, Copy, Debug)]
+pub struct Capability(u32);
+
+impl Capability {
+    /// Resolve the name of a capability such as `chown` or `sys_admin`.
+    ///
+    /// Return `None` if the capability is unknown.
+    pub fn from_name(name: &str) -> Option<Capability> {
+        let name = name.to_lowercase();
+        let name = name.strip_prefix("cap_").unwrap_or(&name);
+        let name = CString::new(name).ok()?;
+        let capability = unsafe { capng_name_to_capability(name.as_ptr()) };
+        if capability < 0 {
+            return None;
+        }
+        Some(Capability(capability as u32))
+    }
+}
+
+impl Deref for Capability {
+    type Target = u32;
+
+    fn deref(&self) -> &u32 {
+        &self.0
+    }
+}
+
+/// Sets of capabilities of a process.
+#[derive(Copy, Clone)]
+#[repr(u32)]
+pub enum Type {
+    Effective = capng_type_t_CAPNG_EFFECTIVE,
+    Permitted = capng_type_t_CAPNG_PERMITTED,
+    Inheritable = capng_type_t_CAPNG_INHERITABLE,
+    BoundingSet = capng_type_t_CAPNG_BOUNDING_SET,
+}
+
+/// Parts of the capabilities to clear or apply.
+#[derive(Copy, Clone)]
+#[repr(u32)]
+pub enum Select {
+    /// The effective, permitted and inheritable sets.
+    Caps = capng_select_t_CAPNG_SELECT_CAPS,
+    /// The bounding set.
+    Bounds = capng_select_t_CAPNG_SELECT_BOUNDS,
+    Both = capng_select_t_CAPNG_SELECT_BOTH,
+}
+
+/// Clear the capabilities in the internal state.
+pub fn clear(select: Select) {
+    unsafe { capng_clear(select as u32) }
+}
+
+/// Add the capability to the sets in the internal state.
+pub fn add(types: &[Type], capability: Capability) -> io::Result<()> {
+    let types = types.iter().fold(0, |types, &t| types | t as u32);
+    let rc = unsafe { capng_update(capng_act_t_CAPNG_ADD, types, *capability) };
+    if rc < 0 {
+        return Err(io::Error::other("Failed to update capabilities"));
+    }
+    Ok(())
+}
+
+/// Apply the internal state to the current process.
+pub fn apply(select: Select) -> io::Result<()> {
+    let rc = unsafe { capng_apply(select as u32) };
+    if rc < 0 {
+        return Err(io::Error::other("Failed to apply capabilities"));
+    }
+    Ok(())
+}
+
+/// Extra features make Command run in a new container.
+pub trait CommandExt {
+    /// Drop all capabilities except the given ones in child process.
+    fn capabilities(&mut self, keep: Vec<Capability>) -> &mut Command;
+}
+
+impl CommandExt for Command {
+    fn capabilities(&mut self, keep: Vec<Capability>) -> &mut Command {
+        unsafe {
+            self.pre_exec(move || {
+                clear(Select::Both);
+                for &capability in &keep {
+                    add(
+                        &[
+                            Type::Effective,
+                            Type::Permitted,
+                            Type::Inheritable,
+                            Type::BoundingSet,
+                        ],
+                        capability,
+                    )?;
+                }
+                apply(Select::Both)
+            });
+        }
+        self
+    }
+}
@@ -28,12 +28,12 @@ where
     P: AsRef<Path>,
 {
     fn write(&mut self, attr: &T) -> io::Result<()> {
-        write(&self, &attr.to_string())?;
+        write(self, attr.to_string())?;
         Ok(())
     }
 
     fn read(&self) -> io::Result<U> {
-        let attr = read_to_string(&self)?
+        let attr = read_to_string(self)?
             .trim()
             .parse()
             .expect("Failed to read the value from the given file");
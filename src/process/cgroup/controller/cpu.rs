@@ -8,31 +8,50 @@ use super::*;
 
 pub struct CpuController<'a, T: 'a + AsRef<Path>> {
     inner: T,
+    version: Version,
     _mark: PhantomData<&'a ()>,
 }
 
 impl<'a, T: 'a + AsRef<Path>> CpuController<'a, T> {
     pub fn period(&'a self) -> Box<dyn AttrFile<'a, Duration, Duration> + 'a> {
-        Box::new(CpuTimeFile {
-            inner: self.inner.as_ref().join("cpu.cfs_period_us"),
-            _mark: PhantomData,
-        })
+        match self.version {
+            Version::V1 => Box::new(CpuTimeFile {
+                inner: self.inner.as_ref().join("cpu.cfs_period_us"),
+                _mark: PhantomData,
+            }),
+            Version::V2 => Box::new(CpuMaxFile {
+                inner: self.inner.as_ref().join("cpu.max"),
+                field: CpuMaxField::Period,
+                _mark: PhantomData,
+            }),
+        }
     }
 
+    /// The cpu time limit in each period.
+    ///
+    /// It is `Duration::MAX` if there is no limit.
     pub fn quota(&'a self) -> Box<dyn AttrFile<'a, Duration, Duration> + 'a> {
-        Box::new(CpuTimeFile {
-            inner: self.inner.as_ref().join("cpu.cfs_quota_us"),
-            _mark: PhantomData,
-        })
+        match self.version {
+            Version::V1 => Box::new(CpuTimeFile {
+                inner: self.inner.as_ref().join("cpu.cfs_quota_us"),
+                _mark: PhantomData,
+            }),
+            Version::V2 => Box::new(CpuMaxFile {
+                inner: self.inner.as_ref().join("cpu.max"),
+                field: CpuMaxField::Quota,
+                _mark: PhantomData,
+            }),
+        }
     }
 }
 
 impl<'a> Controller<'a> for CpuController<'a, PathBuf> {
     const NAME: &'static str = "cpu";
 
-    fn from_ctx(context: &Context) -> CpuController<PathBuf> {
+    fn from_ctx(context: &Context) -> CpuController<'_, PathBuf> {
         CpuController {
-            inner: Context::root().join(Self::NAME).join(&context.name),
+            inner: context.controller_path(Self::NAME),
+            version: context.version,
             _mark: PhantomData,
         }
     }
@@ -60,8 +79,11 @@ struct CpuTimeFile<'a, T: 'a + AsRef<Path>> {
 
 impl<'a, T: 'a + AsRef<Path>> AttrFile<'a, Duration, Duration> for CpuTimeFile<'a, T> {
     fn read(&self) -> io::Result<Duration> {
-        let attr: u64 = read_to_string(&self.inner)?.trim().parse().unwrap();
-        Ok(Duration::from_micros(attr))
+        let attr: i64 = read_to_string(&self.inner)?.trim().parse().unwrap();
+        if attr < 0 {
+            return Ok(Duration::MAX);
+        }
+        Ok(Duration::from_micros(attr as u64))
     }
 
     fn write(&mut self, attr: &Duration) -> io::Result<()> {
@@ -69,3 +91,56 @@ impl<'a, T: 'a + AsRef<Path>> AttrFile<'a, Duration, Duration> for CpuTimeFile<'
         Ok(())
     }
 }
+
+#[derive(Clone, Copy)]
+enum CpuMaxField {
+    Quota,
+    Period,
+}
+
+/// One of the fields in the `cpu.max` file of cgroup v2,
+/// which contains "$QUOTA $PERIOD" in microseconds.
+struct CpuMaxFile<'a, T: 'a + AsRef<Path>> {
+    inner: T,
+    field: CpuMaxField,
+    _mark: PhantomData<&'a T>,
+}
+
+impl<'a, T: 'a + AsRef<Path>> CpuMaxFile<'a, T> {
+    fn read_fields(&self) -> io::Result<(String, String)> {
+        let content = read_to_string(&self.inner)?;
+        let mut fields = content.split_whitespace();
+        match (fields.next(), fields.next()) {
+            (Some(quota), Some(period)) => Ok((quota.to_owned(), period.to_owned())),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid content of cpu.max: {}", content),
+            )),
+        }
+    }
+}
+
+impl<'a, T: 'a + AsRef<Path>> AttrFile<'a, Duration, Duration> for CpuMaxFile<'a, T> {
+    fn read(&self) -> io::Result<Duration> {
+        let (quota, period) = self.read_fields()?;
+        let attr = match self.field {
+            CpuMaxField::Quota => quota,
+            CpuMaxField::Period => period,
+        };
+        if attr == "max" {
+            return Ok(Duration::MAX);
+        }
+        Ok(Duration::from_micros(attr.parse().unwrap()))
+    }
+
+    fn write(&mut self, attr: &Duration) -> io::Result<()> {
+        let (quota, period) = self.read_fields()?;
+        let attr = attr.as_micros().to_string();
+        let content = match self.field {
+            CpuMaxField::Quota => format!("{} {}", attr, period),
+            CpuMaxField::Period => format!("{} {}", quota, attr),
+        };
+        write(&self.inner, content)?;
+        Ok(())
+    }
+}
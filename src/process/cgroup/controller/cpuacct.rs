@@ -8,17 +8,24 @@ use super::*;
 
 pub struct CpuAcctController<'a, T: 'a + AsRef<Path>> {
     inner: T,
+    version: Version,
     _mark: PhantomData<&'a ()>,
 }
 
 impl<'a, T: 'a + AsRef<Path>> CpuAcctController<'a, T> {
     pub fn usage(&self) -> io::Result<Duration> {
+        if self.version == Version::V2 {
+            return self.cpu_stat("usage_usec");
+        }
         let file = self.inner.as_ref().join("cpuacct.usage");
         let usage = read_to_string(&file)?.trim().parse().unwrap();
         Ok(Duration::from_nanos(usage))
     }
 
     pub fn usage_all(&self) -> io::Result<Vec<(usize, Duration, Duration)>> {
+        if self.version == Version::V2 {
+            return Err(unsupported("cpuacct.usage_all", self.version));
+        }
         let file = self.inner.as_ref().join("cpuacct.usage_all");
         let usages = read_to_string(&file)?
             .lines()
@@ -44,6 +51,9 @@ impl<'a, T: 'a + AsRef<Path>> CpuAcctController<'a, T> {
     }
 
     pub fn usage_percpu(&self) -> io::Result<Vec<Duration>> {
+        if self.version == Version::V2 {
+            return Err(unsupported("cpuacct.usage_percpu", self.version));
+        }
         let file = self.inner.as_ref().join("cpuacct.usage_percpu");
         let usages = read_to_string(&file)?
             .split_whitespace()
@@ -54,6 +64,9 @@ impl<'a, T: 'a + AsRef<Path>> CpuAcctController<'a, T> {
     }
 
     pub fn usage_percpu_sys(&self) -> io::Result<Vec<Duration>> {
+        if self.version == Version::V2 {
+            return Err(unsupported("cpuacct.usage_percpu_sys", self.version));
+        }
         let file = self.inner.as_ref().join("cpuacct.usage_percpu_sys");
         let usages = read_to_string(&file)?
             .split_whitespace()
@@ -64,6 +77,9 @@ impl<'a, T: 'a + AsRef<Path>> CpuAcctController<'a, T> {
     }
 
     pub fn usage_percpu_user(&self) -> io::Result<Vec<Duration>> {
+        if self.version == Version::V2 {
+            return Err(unsupported("cpuacct.usage_percpu_user", self.version));
+        }
         let file = self.inner.as_ref().join("cpuacct.usage_percpu_user");
         let usages = read_to_string(&file)?
             .split_whitespace()
@@ -74,24 +90,42 @@ impl<'a, T: 'a + AsRef<Path>> CpuAcctController<'a, T> {
     }
 
     pub fn usage_sys(&self) -> io::Result<Duration> {
+        if self.version == Version::V2 {
+            return self.cpu_stat("system_usec");
+        }
         let file = self.inner.as_ref().join("cpuacct.usage_sys");
         let usage = read_to_string(&file)?.trim().parse().unwrap();
         Ok(Duration::from_nanos(usage))
     }
 
     pub fn usage_user(&self) -> io::Result<Duration> {
+        if self.version == Version::V2 {
+            return self.cpu_stat("user_usec");
+        }
         let file = self.inner.as_ref().join("cpuacct.usage_user");
         let usage = read_to_string(&file)?.trim().parse().unwrap();
         Ok(Duration::from_nanos(usage))
     }
 }
 
+impl<'a, T: 'a + AsRef<Path>> CpuAcctController<'a, T> {
+    /// Read a field in microseconds from `cpu.stat` of cgroup v2.
+    fn cpu_stat(&self, key: &str) -> io::Result<Duration> {
+        let file = self.inner.as_ref().join("cpu.stat");
+        let usage = read_keyed_field(&file, key)?.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "No such field in cpu.stat")
+        })?;
+        Ok(Duration::from_micros(usage))
+    }
+}
+
 impl<'a> Controller<'a> for CpuAcctController<'a, PathBuf> {
     const NAME: &'static str = "cpuacct";
 
-    fn from_ctx(context: &Context) -> CpuAcctController<PathBuf> {
+    fn from_ctx(context: &Context) -> CpuAcctController<'_, PathBuf> {
         CpuAcctController {
-            inner: Context::root().join(Self::NAME).join(&context.name),
+            inner: context.controller_path(Self::NAME),
+            version: context.version,
             _mark: PhantomData,
         }
     }
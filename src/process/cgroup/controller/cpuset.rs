@@ -66,7 +66,7 @@ impl CpusetAllocator {
     fn new() -> CpusetAllocator {
         let cpus = num_cpus::get() as u32;
         let mut avail = BTreeSet::new();
-        avail.insert((0 as u32, (cpus - 1) as u32));
+        avail.insert((0, cpus - 1));
         Self {
             avail,
             avail_cpu_num: cpus,
@@ -81,7 +81,7 @@ impl CpusetAllocator {
         let mut to_insert: Vec<(u32, u32)> = Vec::new();
         let mut cpu_remain = num_of_cpu;
         let mut cpus_to_allocate = Vec::new();
-        self.avail = self.avail.iter().filter(|(start, end)| {
+        self.avail.retain(|(start, end)| {
             let len = end - start + 1;
             let allocated = std::cmp::min(len, cpu_remain);
             cpu_remain -= allocated;
@@ -92,7 +92,7 @@ impl CpusetAllocator {
                 cpus_to_allocate.push((*start, start + allocated - 1));
             }
             allocated == 0
-        }).cloned().collect();
+        });
 
         to_insert.into_iter().for_each(|(start, end)| {
             self.avail.insert((start, end));
@@ -110,7 +110,7 @@ impl CpusetAllocator {
             if rng.1 + 1 >= other.0 {
                 self.avail_cpu_num -= rng.1 - rng.0 + 1;
                 other.0 = rng.0;
-                need_remove.push(rng.clone());
+                need_remove.push(*rng);
             }
         }
 
@@ -118,7 +118,7 @@ impl CpusetAllocator {
             if rng.0 <= other.1 + 1 {
                 self.avail_cpu_num -= rng.1 - rng.0 + 1;
                 other.1 = rng.1;
-                need_remove.push(rng.clone());
+                need_remove.push(*rng);
             }
         }
         need_remove.iter().for_each(|it| {
@@ -204,7 +204,7 @@ impl<'a, T: 'a + AsRef<Path>> CpusetController<'a, T> {
             inner: self.inner.as_ref().join("cpuset.mems"),
             _marker: PhantomData
         };
-        mem_file.write(&vec![(0, 0)]);
+        mem_file.write(&vec![(0, 0)])?;
         file.write(data)?;
         Ok(())
     }
@@ -213,9 +213,9 @@ impl<'a, T: 'a + AsRef<Path>> CpusetController<'a, T> {
 impl<'a> Controller<'a> for CpusetController<'a, PathBuf> {
     const NAME: &'static str = "cpuset";
 
-    fn from_ctx(context: &Context) -> CpusetController<PathBuf> {
+    fn from_ctx(context: &Context) -> CpusetController<'_, PathBuf> {
         CpusetController {
-            inner: Context::root().join(Self::NAME).join(&context.name),
+            inner: context.controller_path(Self::NAME),
             _mark: PhantomData,
         }
     }
@@ -319,16 +319,16 @@ mod test {
         show_avail(cpuset_allocator.clone());
 
 
-        release(cpuset_allocator.clone(), &first_allocated);
+        release(cpuset_allocator.clone(), &first_allocated).unwrap();
         show_avail(cpuset_allocator.clone());
 
-        release(cpuset_allocator.clone(), &third_allocated);
+        release(cpuset_allocator.clone(), &third_allocated).unwrap();
         show_avail(cpuset_allocator.clone());
 
-        release(cpuset_allocator.clone(), &second_allocated);
+        release(cpuset_allocator.clone(), &second_allocated).unwrap();
         show_avail(cpuset_allocator.clone());
 
-        release(cpuset_allocator.clone(), &fourth_allocated);
+        release(cpuset_allocator.clone(), &fourth_allocated).unwrap();
         show_avail(cpuset_allocator.clone());
 
         Ok(())
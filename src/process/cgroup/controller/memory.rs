@@ -1,4 +1,4 @@
-use std::fs::{create_dir, read_to_string};
+use std::fs::{create_dir, read_to_string, write};
 use std::io;
 use std::marker::PhantomData;
 use std::path::Path;
@@ -7,45 +7,129 @@ use super::*;
 
 pub struct MemoryController<'a, T: 'a + AsRef<Path>> {
     inner: T,
+    version: Version,
     _mark: PhantomData<&'a ()>,
 }
 
 impl<'a, T: AsRef<Path>> MemoryController<'a, T> {
+    /// The memory controller of the cgroup in `inner`.
+    pub(in crate::process::cgroup) fn new(inner: T, version: Version) -> MemoryController<'a, T> {
+        MemoryController {
+            inner,
+            version,
+            _mark: PhantomData,
+        }
+    }
+
     pub fn usage_in_bytes(&self) -> io::Result<usize> {
-        let file = self.inner.as_ref().join("memory.usage_in_bytes");
+        let file = match self.version {
+            Version::V1 => self.inner.as_ref().join("memory.usage_in_bytes"),
+            Version::V2 => self.inner.as_ref().join("memory.current"),
+        };
         let usage = read_to_string(&file)?.trim().parse().unwrap();
         Ok(usage)
     }
 
+    /// The peak memory usage.
+    ///
+    /// cgroup v2 tracks it in `memory.peak` since Linux 5.19. On older kernels the current
+    /// usage in `memory.current` is used instead, which is a lower bound of the peak.
     pub fn max_usage_in_bytes(&self) -> io::Result<usize> {
-        let file = self.inner.as_ref().join("memory.max_usage_in_bytes");
-        let usage = read_to_string(&file)?.trim().parse().unwrap();
-        Ok(usage)
+        let file = match self.version {
+            Version::V1 => self.inner.as_ref().join("memory.max_usage_in_bytes"),
+            Version::V2 => self.inner.as_ref().join("memory.peak"),
+        };
+        let usage = match read_to_string(&file) {
+            Ok(usage) => usage,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound && self.version == Version::V2 => {
+                return self.usage_in_bytes();
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(usage.trim().parse().unwrap())
     }
 
+    /// The memory limit, which is `usize::MAX` if there is no limit in cgroup v2.
     pub fn limit_in_bytes(&'a self) -> Box<dyn AttrFile<'a, usize, usize> + 'a> {
-        Box::new(self.inner.as_ref().join("memory.limit_in_bytes"))
+        match self.version {
+            Version::V1 => Box::new(self.inner.as_ref().join("memory.limit_in_bytes")),
+            Version::V2 => Box::new(MemoryMaxFile {
+                inner: self.inner.as_ref().join("memory.max"),
+            }),
+        }
+    }
+
+    /// The limit of memory and swap usage.
+    ///
+    /// cgroup v2 limits the swap usage only, so the limit of swap
+    /// is set to the difference between it and the memory limit.
+    pub fn memsw_limit_in_bytes(&'a self) -> Box<dyn AttrFile<'a, usize, usize> + 'a> {
+        match self.version {
+            Version::V1 => Box::new(self.inner.as_ref().join("memory.memsw.limit_in_bytes")),
+            Version::V2 => Box::new(MemswMaxFile {
+                memory: MemoryMaxFile {
+                    inner: self.inner.as_ref().join("memory.max"),
+                },
+                swap: MemoryMaxFile {
+                    inner: self.inner.as_ref().join("memory.swap.max"),
+                },
+            }),
+        }
     }
 
+    /// Whether the swap usage can be limited.
+    pub fn memsw_supported(&self) -> bool {
+        match self.version {
+            Version::V1 => self.inner.as_ref().join("memory.memsw.limit_in_bytes"),
+            Version::V2 => self.inner.as_ref().join("memory.swap.max"),
+        }
+        .exists()
+    }
+
+    /// Count of times the memory usage hits the limit.
     pub fn failcnt(&self) -> io::Result<usize> {
-        let file = self.inner.as_ref().join("memory.failcnt");
-        let count = read_to_string(&file)?.trim().parse().unwrap();
-        Ok(count)
+        match self.version {
+            Version::V1 => {
+                let file = self.inner.as_ref().join("memory.failcnt");
+                let count = read_to_string(&file)?.trim().parse().unwrap();
+                Ok(count)
+            }
+            Version::V2 => {
+                let file = self.inner.as_ref().join("memory.events");
+                Ok(read_keyed_field(&file, "max")?.unwrap_or(0) as usize)
+            }
+        }
+    }
+
+    /// Count of processes killed by the OOM killer in the cgroup.
+    ///
+    /// Return `None` if the kernel does not report it in `memory.oom_control`.
+    pub fn oom_kill(&self) -> io::Result<Option<usize>> {
+        let file = match self.version {
+            Version::V1 => self.inner.as_ref().join("memory.oom_control"),
+            Version::V2 => self.inner.as_ref().join("memory.events"),
+        };
+        let count = read_keyed_field(&file, "oom_kill")?;
+        Ok(count.map(|count| count as usize))
     }
 
+    /// The swappiness of the cgroup, which is not supported by cgroup v2.
     pub fn swappiness(&'a self) -> Box<dyn AttrFile<'a, usize, usize> + 'a> {
-        Box::new(self.inner.as_ref().join("memory.swappiness"))
+        match self.version {
+            Version::V1 => Box::new(self.inner.as_ref().join("memory.swappiness")),
+            Version::V2 => Box::new(UnsupportedFile {
+                name: "memory.swappiness",
+                version: self.version,
+            }),
+        }
     }
 }
 
 impl<'a> Controller<'a> for MemoryController<'a, PathBuf> {
     const NAME: &'static str = "memory";
 
-    fn from_ctx(context: &Context) -> MemoryController<PathBuf> {
-        MemoryController {
-            inner: Context::root().join(Self::NAME).join(&context.name),
-            _mark: PhantomData,
-        }
+    fn from_ctx(context: &Context) -> MemoryController<'_, PathBuf> {
+        MemoryController::new(context.controller_path(Self::NAME), context.version)
     }
 
     fn initialize(&self) -> io::Result<()> {
@@ -63,3 +147,59 @@ impl<'a, T: 'a + AsRef<Path>> AsRef<Path> for MemoryController<'a, T> {
         self.inner.as_ref()
     }
 }
+
+/// A memory limit file of cgroup v2 which contains `max` if there is no limit.
+struct MemoryMaxFile {
+    inner: PathBuf,
+}
+
+impl<'a> AttrFile<'a, usize, usize> for MemoryMaxFile {
+    fn write(&mut self, attr: &usize) -> io::Result<()> {
+        if *attr == usize::MAX {
+            write(&self.inner, "max")
+        } else {
+            write(&self.inner, attr.to_string())
+        }
+    }
+
+    fn read(&self) -> io::Result<usize> {
+        let attr = read_to_string(&self.inner)?;
+        match attr.trim() {
+            "max" => Ok(usize::MAX),
+            attr => Ok(attr.parse().unwrap()),
+        }
+    }
+}
+
+/// The limit of memory and swap usage in cgroup v2.
+struct MemswMaxFile {
+    memory: MemoryMaxFile,
+    swap: MemoryMaxFile,
+}
+
+impl<'a> AttrFile<'a, usize, usize> for MemswMaxFile {
+    fn write(&mut self, attr: &usize) -> io::Result<()> {
+        let memory = self.memory.read()?;
+        self.swap.write(&attr.saturating_sub(memory))
+    }
+
+    fn read(&self) -> io::Result<usize> {
+        Ok(self.memory.read()?.saturating_add(self.swap.read()?))
+    }
+}
+
+/// A file which does not exist in this version of cgroup.
+struct UnsupportedFile {
+    name: &'static str,
+    version: Version,
+}
+
+impl<'a> AttrFile<'a, usize, usize> for UnsupportedFile {
+    fn write(&mut self, _attr: &usize) -> io::Result<()> {
+        Err(unsupported(self.name, self.version))
+    }
+
+    fn read(&self) -> io::Result<usize> {
+        Err(unsupported(self.name, self.version))
+    }
+}
@@ -2,12 +2,13 @@ mod cpu;
 mod cpuacct;
 mod memory;
 mod cpuset;
+mod pids;
 
 use std::io;
 
 use super::*;
 
-pub use self::{cpu::*, cpuacct::*, memory::*, cpuset::*};
+pub use self::{cpu::*, cpuacct::*, memory::*, cpuset::*, pids::*};
 
 /// Cgroup controller trait.
 ///
@@ -0,0 +1,84 @@
+use std::fs::{create_dir, read_to_string, write};
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use super::*;
+
+pub struct PidsController<'a, T: 'a + AsRef<Path>> {
+    inner: T,
+    _mark: PhantomData<&'a ()>,
+}
+
+impl<'a, T: 'a + AsRef<Path>> PidsController<'a, T> {
+    /// Number of processes and threads in the cgroup.
+    pub fn current(&self) -> io::Result<usize> {
+        let file = self.inner.as_ref().join("pids.current");
+        let current = read_to_string(&file)?.trim().parse().unwrap();
+        Ok(current)
+    }
+
+    /// Max number of processes and threads, which is `usize::MAX` if there is no limit.
+    pub fn max(&'a self) -> Box<dyn AttrFile<'a, usize, usize> + 'a> {
+        Box::new(PidsMaxFile {
+            inner: self.inner.as_ref().join("pids.max"),
+            _mark: PhantomData,
+        })
+    }
+
+    /// Count of times a fork failed because of the limit.
+    pub fn failcnt(&self) -> io::Result<usize> {
+        let file = self.inner.as_ref().join("pids.events");
+        Ok(read_keyed_field(&file, "max")?.unwrap_or(0) as usize)
+    }
+}
+
+impl<'a> Controller<'a> for PidsController<'a, PathBuf> {
+    const NAME: &'static str = "pids";
+
+    fn from_ctx(context: &Context) -> PidsController<'_, PathBuf> {
+        PidsController {
+            inner: context.controller_path(Self::NAME),
+            _mark: PhantomData,
+        }
+    }
+
+    fn initialize(&self) -> io::Result<()> {
+        match create_dir(&self.inner) {
+            Ok(_) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+}
+
+impl<'a, T: 'a + AsRef<Path>> AsRef<Path> for PidsController<'a, T> {
+    fn as_ref(&self) -> &Path {
+        self.inner.as_ref()
+    }
+}
+
+/// The `pids.max` file which contains `max` if there is no limit.
+struct PidsMaxFile<'a, T: 'a + AsRef<Path>> {
+    inner: T,
+    _mark: PhantomData<&'a T>,
+}
+
+impl<'a, T: 'a + AsRef<Path>> AttrFile<'a, usize, usize> for PidsMaxFile<'a, T> {
+    fn write(&mut self, attr: &usize) -> io::Result<()> {
+        if *attr == usize::MAX {
+            write(&self.inner, "max")
+        } else {
+            write(&self.inner, attr.to_string())
+        }
+    }
+
+    fn read(&self) -> io::Result<usize> {
+        let attr = read_to_string(&self.inner)?;
+        match attr.trim() {
+            "max" => Ok(usize::MAX),
+            attr => Ok(attr.parse().unwrap()),
+        }
+    }
+}
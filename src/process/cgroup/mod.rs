@@ -1,16 +1,21 @@
 //! High-level APIs for cgroup (Linux control group).
+//!
+//! Both cgroup v1 and cgroup v2 (the unified hierarchy) are supported.
+//! The version is detected at runtime and the controllers choose
+//! their files by it, so the APIs are the same for both versions.
 mod attr_file;
 mod controller;
 mod hierarchy;
 
-use std::fs::remove_dir;
+use std::fs::{create_dir, read_to_string, remove_dir, write};
 use std::io;
 use std::os::unix::process::CommandExt as _;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::time::SystemTime;
-use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use std::sync::{Arc, RwLock};
 
+use lazy_static::lazy_static;
 use nix::unistd::Pid;
 use rand;
 
@@ -20,14 +25,111 @@ pub use hierarchy::*;
 
 const CGROUP_ROOT: &str = "/sys/fs/cgroup";
 
+/// Max times of killing the processes in a cgroup without `cgroup.kill`.
+const MAX_KILL_ROUNDS: usize = 16;
+
+lazy_static! {
+    static ref VERSION: Version = Version::detect();
+    static ref DELEGATED_ROOT: RwLock<Option<PathBuf>> = RwLock::new(None);
+}
+
+/// Version of the cgroup filesystem.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Version {
+    /// One hierarchy for each controller under `/sys/fs/cgroup/<controller>`.
+    V1,
+    /// The unified hierarchy mounted at `/sys/fs/cgroup`.
+    V2,
+}
+
+impl Version {
+    fn detect() -> Version {
+        if Path::new(CGROUP_ROOT).join("cgroup.controllers").exists() {
+            Version::V2
+        } else {
+            Version::V1
+        }
+    }
+}
+
+/// Version of the cgroup filesystem used on this host.
+pub fn version() -> Version {
+    *VERSION
+}
+
+/// Path of the cgroup v2 directory which the current process belongs to.
+pub fn current() -> io::Result<PathBuf> {
+    if version() != Version::V2 {
+        return Err(unsupported("The current cgroup", version()));
+    }
+    read_to_string("/proc/self/cgroup")?
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| Path::new(CGROUP_ROOT).join(path.trim_start_matches('/')))
+        .ok_or_else(|| unsupported("The current cgroup", version()))
+}
+
+/// Create cgroups under a delegated cgroup v2 subtree instead of the root.
+///
+/// The delegated directory must be writable by the current user, such as the one
+/// created by systemd with `Delegate=yes`. Since a cgroup with controllers enabled for
+/// its children must not contain any process, the current process is moved into
+/// the `supervisor` child of it.
+pub fn delegate<P: AsRef<Path>>(root: P) -> io::Result<()> {
+    let root = root.as_ref();
+    if version() != Version::V2 {
+        return Err(unsupported("Delegation", version()));
+    }
+    let supervisor = root.join("supervisor");
+    if !supervisor.exists() {
+        create_dir(&supervisor)?;
+    }
+    write(supervisor.join("cgroup.procs"), "0")?;
+    *DELEGATED_ROOT.write().unwrap() = Some(root.to_owned());
+    log::info!("Use the delegated cgroup {}", root.display());
+    Ok(())
+}
+
+/// Root of the cgroups created by the builder.
+///
+/// It is the delegated subtree if any, otherwise the root of the cgroup filesystem.
+pub fn root() -> PathBuf {
+    match DELEGATED_ROOT.read().unwrap().as_ref() {
+        Some(root) => root.clone(),
+        None => PathBuf::from(CGROUP_ROOT),
+    }
+}
+
+/// Error of reading or writing a file which does not exist in this version.
+fn unsupported(file: &str, version: Version) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} is not supported by cgroup {:?}", file, version),
+    )
+}
+
+/// Read a field from a flat keyed file such as `cpu.stat` and `memory.events`.
+fn read_keyed_field(file: &Path, key: &str) -> io::Result<Option<u64>> {
+    let value = read_to_string(file)?.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        match (fields.next(), fields.next()) {
+            (Some(field), Some(value)) if field == key => value.parse().ok(),
+            _ => None,
+        }
+    });
+    Ok(value)
+}
+
 /// Cgroup context.
 #[derive(Debug, Clone)]
 pub struct Context {
     name: String,
+    version: Version,
     cpu_controller_enable: bool,
     cpuacct_controller_enable: bool,
     cpuset_controller_enable: bool,
     memory_controller_enable: bool,
+    pids_controller_enable: bool,
     // cpuset_controller need to be holded by Context, which maintain the allocated cpu
     cpuset_data: Option<Arc<CpusetData>>,
 }
@@ -36,9 +138,9 @@ impl Context {
     /// Get the cpu controller.
     ///
     /// Return `None` if the controller has not been initialized.
-    pub fn cpu_controller(&self) -> Option<CpuController<PathBuf>> {
+    pub fn cpu_controller(&self) -> Option<CpuController<'_, PathBuf>> {
         if self.cpu_controller_enable {
-            Some(CpuController::from_ctx(&self))
+            Some(CpuController::from_ctx(self))
         } else {
             None
         }
@@ -47,9 +149,9 @@ impl Context {
     /// Get the cpuacct controller.
     ///
     /// Return `None` if the controller has not been initialized.
-    pub fn cpuacct_controller(&self) -> Option<CpuAcctController<PathBuf>> {
+    pub fn cpuacct_controller(&self) -> Option<CpuAcctController<'_, PathBuf>> {
         if self.cpuacct_controller_enable {
-            Some(CpuAcctController::from_ctx(&self))
+            Some(CpuAcctController::from_ctx(self))
         } else {
             None
         }
@@ -58,9 +160,9 @@ impl Context {
     /// Get the cpuset controller.
     ///
     /// Return `None` if the controller has not been initialized. 
-    pub fn cpuset_controller(&self) -> Option<CpusetController<PathBuf>> {
+    pub fn cpuset_controller(&self) -> Option<CpusetController<'_, PathBuf>> {
         if self.cpuset_controller_enable {
-            Some(CpusetController::from_ctx(&self))
+            Some(CpusetController::from_ctx(self))
         } else {
             None
         }
@@ -69,9 +171,20 @@ impl Context {
     /// Get the cpuacct controller.
     ///
     /// Return `None` if the controller has not been initialized.
-    pub fn memory_controller(&self) -> Option<MemoryController<PathBuf>> {
+    pub fn memory_controller(&self) -> Option<MemoryController<'_, PathBuf>> {
         if self.memory_controller_enable {
-            Some(MemoryController::from_ctx(&self))
+            Some(MemoryController::from_ctx(self))
+        } else {
+            None
+        }
+    }
+
+    /// Get the pids controller.
+    ///
+    /// Return `None` if the controller has not been initialized.
+    pub fn pids_controller(&self) -> Option<PidsController<'_, PathBuf>> {
+        if self.pids_controller_enable {
+            Some(PidsController::from_ctx(self))
         } else {
             None
         }
@@ -86,13 +199,51 @@ impl Context {
     }
 
     /// Add a task(thread) to the context.
+    ///
+    /// It is not supported by cgroup v2 unless the cgroup is threaded.
     pub fn add_task(&mut self, pid: Pid) -> io::Result<()> {
+        if self.version == Version::V2 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Adding a task is not supported by cgroup v2",
+            ));
+        }
         for hierarchy in self.hierarchies() {
             hierarchy.tasks().write(&pid)?;
         }
         Ok(())
     }
 
+    /// Kill all processes in the context.
+    ///
+    /// `cgroup.kill` is used if it is supported. Otherwise the processes are killed
+    /// one by one until none is left, since they may fork in the meantime.
+    pub fn kill(&self) -> io::Result<()> {
+        if self.version == Version::V2 {
+            let kill_file = root().join(&self.name).join("cgroup.kill");
+            if kill_file.exists() {
+                return write(kill_file, "1");
+            }
+        }
+        for hierarchy in self.hierarchies() {
+            for _ in 0..MAX_KILL_ROUNDS {
+                let procs = hierarchy.procs().read()?;
+                if procs.is_empty() {
+                    break;
+                }
+                for pid in procs {
+                    let _ = nix::sys::signal::kill(pid, nix::sys::signal::SIGKILL);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove all hierarchies of the context.
+    ///
+    /// # Safety
+    ///
+    /// The context must not be used any more after it is removed.
     pub unsafe fn remove(&self) -> io::Result<()> {
         for hierarchy in self.hierarchies() {
             remove_dir(hierarchy.path())?;
@@ -102,14 +253,23 @@ impl Context {
 }
 
 impl Context {
-    /// Root path of the cgroup filesystem.
-    fn root() -> &'static Path {
-        Path::new(CGROUP_ROOT)
+    /// Path of the controller in this context.
+    ///
+    /// All controllers share the same path in cgroup v2.
+    fn controller_path(&self, controller: &str) -> PathBuf {
+        match self.version {
+            Version::V1 => root().join(controller).join(&self.name),
+            Version::V2 => root().join(&self.name),
+        }
     }
 
     /// All hierarchies that this cgroup context contains.
-    fn hierarchies<'a>(&'a self) -> Vec<Box<dyn 'a + Hierarchy>> {
+    fn hierarchies<'a>(&'a self) -> Vec<Box<dyn 'a + Hierarchy<'a>>> {
         let mut res: Vec<Box<dyn Hierarchy>> = Vec::new();
+        if self.version == Version::V2 {
+            res.push(Box::new(root().join(&self.name)));
+            return res;
+        }
         if let Some(controller) = self.cpu_controller() {
             res.push(Box::new(controller));
         }
@@ -122,6 +282,9 @@ impl Context {
         if let Some(controller) = self.cpuset_controller() {
             res.push(Box::new(controller));
         }
+        if let Some(controller) = self.pids_controller() {
+            res.push(Box::new(controller));
+        }
         res
     }
 }
@@ -149,6 +312,11 @@ pub struct Builder {
     memory_controller: bool,
     cpuset_controller: bool,
     cpuset_num: u32,
+    pids_controller: bool,
+    pids_limit: Option<usize>,
+    memory_limit: Option<usize>,
+    memsw_limit: Option<usize>,
+    cpu_quota: Option<(Duration, Duration)>,
 }
 
 impl Builder {
@@ -183,8 +351,45 @@ impl Builder {
         self
     }
 
+    pub fn pids_controller(mut self, flag: bool) -> Builder {
+        self.pids_controller = flag;
+        self
+    }
+
+    /// Limit the number of processes and threads.
+    ///
+    /// It only takes effect with the pids controller.
+    pub fn pids_limit(mut self, limit: usize) -> Builder {
+        self.pids_limit = Some(limit);
+        self
+    }
+
+    /// Limit the memory usage in bytes.
+    ///
+    /// It only takes effect with the memory controller.
+    pub fn memory_limit(mut self, limit: usize) -> Builder {
+        self.memory_limit = Some(limit);
+        self
+    }
+
+    /// Limit the memory and swap usage in bytes.
+    ///
+    /// It only takes effect with the memory controller.
+    pub fn memsw_limit(mut self, limit: usize) -> Builder {
+        self.memsw_limit = Some(limit);
+        self
+    }
+
+    /// Limit the cpu time to `quota` in each `period`.
+    ///
+    /// It only takes effect with the cpu controller.
+    pub fn cpu_quota(mut self, period: Duration, quota: Duration) -> Builder {
+        self.cpu_quota = Some((period, quota));
+        self
+    }
+
     pub async fn build(self) -> io::Result<Context> {
-        let name = match self.name {
+        let name = match self.name.clone() {
             Some(name) => name,
             None => {
                 let timestamp = SystemTime::now()
@@ -196,18 +401,29 @@ impl Builder {
             }
         };
 
+        let version = version();
+        if version == Version::V2 {
+            self.enable_controllers()?;
+        }
+
         let mut ctx = Context {
             name,
+            version,
             cpu_controller_enable: self.cpu_controller,
             cpuacct_controller_enable: self.cpuacct_controller,
             cpuset_controller_enable: self.cpuset_controller,
             memory_controller_enable: self.memory_controller,
+            pids_controller_enable: self.pids_controller,
             cpuset_data: None,
         };
 
         if self.cpu_controller {
             let controller = CpuController::from_ctx(&ctx);
-            controller.initialize()?
+            controller.initialize()?;
+            if let Some((period, quota)) = self.cpu_quota {
+                controller.period().write(&period)?;
+                controller.quota().write(&quota)?;
+            }
         }
 
         if self.cpuacct_controller {
@@ -217,7 +433,25 @@ impl Builder {
 
         if self.memory_controller {
             let controller = MemoryController::from_ctx(&ctx);
-            controller.initialize()?
+            controller.initialize()?;
+            if let Some(limit) = self.memory_limit {
+                controller.limit_in_bytes().write(&limit)?;
+            }
+            if let Some(limit) = self.memsw_limit {
+                if controller.memsw_supported() {
+                    controller.memsw_limit_in_bytes().write(&limit)?;
+                } else {
+                    log::debug!("Swap accounting is not supported, skip the memsw limit");
+                }
+            }
+        }
+
+        if self.pids_controller {
+            let controller = PidsController::from_ctx(&ctx);
+            controller.initialize()?;
+            if let Some(limit) = self.pids_limit {
+                controller.max().write(&limit)?;
+            }
         }
 
         if self.cpuset_controller {
@@ -232,6 +466,28 @@ impl Builder {
     }
 }
 
+impl Builder {
+    /// Enable the controllers for the children of the root in cgroup v2.
+    ///
+    /// The controllers must be delegated to the root if it is not the root of the filesystem.
+    fn enable_controllers(&self) -> io::Result<()> {
+        let controllers = [
+            (self.cpu_controller, CpuController::NAME),
+            (self.memory_controller, MemoryController::NAME),
+            (self.cpuset_controller, CpusetController::NAME),
+            (self.pids_controller, PidsController::NAME),
+        ];
+        let subtree_control = root().join("cgroup.subtree_control");
+        let enabled = read_to_string(&subtree_control)?;
+        for (_, name) in controllers.iter().filter(|(flag, _)| *flag) {
+            if !enabled.split_whitespace().any(|enabled| enabled == *name) {
+                write(&subtree_control, format!("+{}", name))?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Default for Builder {
     fn default() -> Builder {
         Builder {
@@ -240,7 +496,12 @@ impl Default for Builder {
             cpuacct_controller: true,
             memory_controller: true,
             cpuset_controller: false,
-            cpuset_num: 0
+            cpuset_num: 0,
+            pids_controller: false,
+            pids_limit: None,
+            memory_limit: None,
+            memsw_limit: None,
+            cpu_quota: None,
         }
     }
 }
@@ -1,38 +1,51 @@
 use super::*;
 
+use std::io::Write;
 use std::iter::FromIterator;
 use std::time::Duration;
 
 #[tokio::test]
 async fn test_cgroup_path() -> io::Result<()> {
     let ctx = Builder::new().build().await?;
+    let path_of = |controller: &str| match version() {
+        Version::V1 => PathBuf::from_iter(&["/sys/fs/cgroup/", controller, ctx.name.as_str()]),
+        Version::V2 => PathBuf::from_iter(&["/sys/fs/cgroup/", ctx.name.as_str()]),
+    };
+
     let cpu_controller = ctx.cpu_controller().unwrap();
     let cpu_path = cpu_controller.as_ref();
     assert!(cpu_path.exists());
-    assert_eq!(
-        cpu_path,
-        PathBuf::from_iter(&["/sys/fs/cgroup/cpu/", ctx.name.as_str()])
-    );
+    assert_eq!(cpu_path, path_of("cpu"));
 
     let cpuacct_controller = ctx.cpuacct_controller().unwrap();
     let cpuact_path = cpuacct_controller.as_ref();
     assert!(cpuact_path.exists());
-    assert_eq!(
-        cpuact_path,
-        PathBuf::from_iter(&["/sys/fs/cgroup/cpuacct/", ctx.name.as_str()])
-    );
+    assert_eq!(cpuact_path, path_of("cpuacct"));
 
     let memory_controller = ctx.memory_controller().unwrap();
     let memory_path = memory_controller.as_ref();
     assert!(memory_path.exists());
-    assert_eq!(
-        memory_path,
-        PathBuf::from_iter(&["/sys/fs/cgroup/memory/", ctx.name.as_str()])
-    );
+    assert_eq!(memory_path, path_of("memory"));
 
     Ok(())
 }
 
+#[test]
+fn test_version() {
+    let unified = Path::new("/sys/fs/cgroup/cgroup.controllers").exists();
+    assert_eq!(version() == Version::V2, unified);
+}
+
+#[test]
+fn test_read_keyed_field() -> io::Result<()> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(b"usage_usec 1024\nuser_usec 1000\nsystem_usec 24\n")?;
+    assert_eq!(read_keyed_field(file.path(), "usage_usec")?, Some(1024));
+    assert_eq!(read_keyed_field(file.path(), "system_usec")?, Some(24));
+    assert_eq!(read_keyed_field(file.path(), "nr_periods")?, None);
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_cpu_controller() -> io::Result<()> {
     let ctx = Builder::new().build().await?;
@@ -96,3 +109,90 @@ async fn test_memory_controller() -> io::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_memory_peak_fallback() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let memory_controller = MemoryController::new(dir.path(), Version::V2);
+    std::fs::write(dir.path().join("memory.current"), "4096\n")?;
+    // kernels before 5.19 do not have memory.peak
+    assert_eq!(memory_controller.max_usage_in_bytes()?, 4096);
+    std::fs::write(dir.path().join("memory.peak"), "8192\n")?;
+    assert_eq!(memory_controller.max_usage_in_bytes()?, 8192);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_builder_limits() -> io::Result<()> {
+    let ctx = Builder::new()
+        .memory_limit(64 * 1024 * 1024)
+        .memsw_limit(128 * 1024 * 1024)
+        .cpu_quota(Duration::from_millis(100), Duration::from_millis(50))
+        .build()
+        .await?;
+
+    let memory_controller = ctx.memory_controller().unwrap();
+    assert_eq!(memory_controller.limit_in_bytes().read()?, 64 * 1024 * 1024);
+    assert_eq!(
+        memory_controller.memsw_limit_in_bytes().read()?,
+        128 * 1024 * 1024
+    );
+
+    let cpu_controller = ctx.cpu_controller().unwrap();
+    assert_eq!(cpu_controller.period().read()?, Duration::from_millis(100));
+    assert_eq!(cpu_controller.quota().read()?, Duration::from_millis(50));
+
+    unsafe { ctx.remove() }
+}
+
+#[tokio::test]
+async fn test_pids_controller() -> io::Result<()> {
+    let ctx = Builder::new()
+        .pids_controller(true)
+        .pids_limit(16)
+        .build()
+        .await?;
+
+    let pids_controller = ctx.pids_controller().unwrap();
+    assert_eq!(pids_controller.max().read()?, 16);
+    assert_eq!(pids_controller.current()?, 0);
+    assert_eq!(pids_controller.failcnt()?, 0);
+
+    pids_controller.max().write(&usize::MAX)?;
+    assert_eq!(pids_controller.max().read()?, usize::MAX);
+
+    unsafe { ctx.remove() }
+}
+
+#[tokio::test]
+async fn test_kill() -> io::Result<()> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let ctx = Builder::new().build().await?;
+    let mut children = Vec::new();
+    for _ in 0..2 {
+        children.push(
+            Command::new("/bin/sh")
+                .arg("-c")
+                .arg("sleep 100 & wait")
+                .cgroup(ctx.clone())
+                .spawn()?,
+        );
+    }
+
+    ctx.kill()?;
+    for child in children.iter_mut() {
+        assert_eq!(child.wait()?.signal(), Some(nix::libc::SIGKILL));
+    }
+    // the orphaned processes exit asynchronously
+    let controller = ctx.cpuacct_controller().unwrap();
+    for _ in 0..100 {
+        if controller.procs().read()?.is_empty() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(controller.procs().read()?.is_empty());
+
+    unsafe { ctx.remove() }
+}
@@ -0,0 +1,190 @@
+//! Detect the isolation features available on this host.
+use std::fmt;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use nix::libc;
+use nix::sched::CloneFlags;
+use nix::unistd::{access, AccessFlags};
+
+use super::cgroup;
+
+/// Isolation features available for running programs.
+#[derive(Debug, Clone)]
+pub struct Features {
+    /// Whether Ana runs without root.
+    pub rootless: bool,
+    /// Whether a user namespace can be created.
+    pub user_namespace: bool,
+    /// Whether the IPC, network, mount, PID and UTS namespaces can be created.
+    pub namespaces: bool,
+    pub cgroup_version: cgroup::Version,
+    /// Root of the cgroups created for programs.
+    pub cgroup_root: PathBuf,
+    /// Whether cgroups can be created under the root.
+    pub cgroup_writable: bool,
+    /// Controllers available under the root.
+    pub cgroup_controllers: Vec<String>,
+    /// CPUs which can be allocated by the cpuset controller.
+    pub cpuset_cpus: Option<String>,
+    /// Whether the overlay filesystem for the rootfs is supported.
+    pub overlay: bool,
+    pub seccomp: bool,
+    pub capabilities: bool,
+}
+
+impl Features {
+    pub fn detect() -> Features {
+        let rootless = super::rootless();
+        let mut flags = CloneFlags::CLONE_NEWIPC
+            | CloneFlags::CLONE_NEWNET
+            | CloneFlags::CLONE_NEWNS
+            | CloneFlags::CLONE_NEWPID
+            | CloneFlags::CLONE_NEWUTS;
+        if rootless {
+            // the other namespaces can only be created in a user namespace without root
+            flags |= CloneFlags::CLONE_NEWUSER;
+        }
+        let cgroup_version = cgroup::version();
+        let cgroup_root = cgroup::root();
+        Features {
+            rootless,
+            user_namespace: can_unshare(CloneFlags::CLONE_NEWUSER),
+            namespaces: can_unshare(flags),
+            cgroup_version,
+            cgroup_writable: access(&cgroup_root, AccessFlags::W_OK).is_ok(),
+            cgroup_controllers: cgroup_controllers(cgroup_version, &cgroup_root),
+            cpuset_cpus: cpuset_cpus(cgroup_version, &cgroup_root),
+            overlay: read_to_string("/proc/filesystems")
+                .unwrap_or_default()
+                .lines()
+                .any(|line| line.split_whitespace().last() == Some("overlay")),
+            cgroup_root,
+            seccomp: cfg!(feature = "seccomp"),
+            capabilities: cfg!(feature = "cap-ng"),
+        }
+    }
+
+    /// Whether programs can be judged with the isolation features.
+    pub fn is_sufficient(&self) -> bool {
+        self.namespaces
+            && self.cgroup_writable
+            && self.missing_controllers().is_empty()
+            && (!self.rootless || self.user_namespace)
+    }
+
+    /// Controllers used by default which are not available.
+    pub fn missing_controllers(&self) -> Vec<&'static str> {
+        let required: &[&str] = match self.cgroup_version {
+            cgroup::Version::V1 => &["cpu", "cpuacct", "cpuset", "memory"],
+            // the cpu usage is accounted by the cpu controller in cgroup v2
+            cgroup::Version::V2 => &["cpu", "cpuset", "memory"],
+        };
+        required
+            .iter()
+            .filter(|controller| !self.cgroup_controllers.iter().any(|c| c == *controller))
+            .copied()
+            .collect()
+    }
+}
+
+impl fmt::Display for Features {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn available(flag: bool) -> &'static str {
+            if flag {
+                "available"
+            } else {
+                "unavailable"
+            }
+        }
+
+        writeln!(
+            f,
+            "Rootless mode: {}",
+            if self.rootless { "yes" } else { "no" }
+        )?;
+        writeln!(f, "User namespace: {}", available(self.user_namespace))?;
+        writeln!(f, "Other namespaces: {}", available(self.namespaces))?;
+        writeln!(
+            f,
+            "Cgroup {:?} at {}: {}",
+            self.cgroup_version,
+            self.cgroup_root.display(),
+            if self.cgroup_writable {
+                "writable"
+            } else {
+                "not writable"
+            }
+        )?;
+        writeln!(
+            f,
+            "Cgroup controllers: {}",
+            self.cgroup_controllers.join(" ")
+        )?;
+        let missing_controllers = self.missing_controllers();
+        if !missing_controllers.is_empty() {
+            writeln!(
+                f,
+                "Missing cgroup controllers: {}",
+                missing_controllers.join(" ")
+            )?;
+        }
+        match self.cpuset_cpus.as_ref() {
+            Some(cpus) => writeln!(f, "Cpuset: cpus {}", cpus)?,
+            None => writeln!(f, "Cpuset: unavailable")?,
+        }
+        writeln!(f, "Overlay filesystem: {}", available(self.overlay))?;
+        writeln!(f, "Seccomp: {}", available(self.seccomp))?;
+        write!(f, "Capabilities: {}", available(self.capabilities))
+    }
+}
+
+/// Check whether the namespaces can be created in a child process.
+fn can_unshare(flags: CloneFlags) -> bool {
+    unsafe {
+        match libc::fork() {
+            -1 => false,
+            0 => libc::_exit(if libc::unshare(flags.bits()) == 0 {
+                0
+            } else {
+                1
+            }),
+            child => {
+                let mut status = 0;
+                libc::waitpid(child, &mut status, 0) == child
+                    && libc::WIFEXITED(status)
+                    && libc::WEXITSTATUS(status) == 0
+            }
+        }
+    }
+}
+
+/// Controllers which can be enabled under the root.
+fn cgroup_controllers(version: cgroup::Version, root: &Path) -> Vec<String> {
+    match version {
+        cgroup::Version::V1 => ["cpu", "cpuacct", "cpuset", "memory", "pids"]
+            .iter()
+            .filter(|controller| root.join(controller).exists())
+            .map(|controller| controller.to_string())
+            .collect(),
+        cgroup::Version::V2 => read_to_string(root.join("cgroup.controllers"))
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(String::from)
+            .collect(),
+    }
+}
+
+/// CPUs of the root which can be allocated to the cgroups of programs.
+fn cpuset_cpus(version: cgroup::Version, root: &Path) -> Option<String> {
+    let file = match version {
+        cgroup::Version::V1 => root.join("cpuset").join("cpuset.effective_cpus"),
+        cgroup::Version::V2 => root.join("cpuset.cpus.effective"),
+    };
+    let cpus = read_to_string(file).ok()?.trim().to_string();
+    if cpus.is_empty() {
+        None
+    } else {
+        Some(cpus)
+    }
+}
@@ -3,6 +3,7 @@
 #[cfg(feature = "cap-ng")]
 pub mod capng;
 pub mod cgroup;
+pub mod features;
 #[cfg(feature = "seccomp")]
 pub mod seccomp;
 
@@ -15,33 +16,89 @@ use std::thread;
 use std::time::Duration;
 
 use nix;
+use nix::libc;
 
 /// Extra features make `Command` run in a new container.
 pub trait CommandExt {
     /// Run program with all namespaces unshared.
+    ///
+    /// Root in the new user namespace is mapped to `nobody`.
     fn unshare_all_ns(&mut self) -> &mut Command;
 
+    /// Run program in the namespaces given by `flags`.
+    ///
+    /// With `CLONE_NEWUSER`, the program enters a new user namespace first,
+    /// in which root is mapped to `uid` and `gid` outside, and the other namespaces
+    /// are owned by it.
+    ///
+    /// With `CLONE_NEWPID`, a minimal init process runs as PID 1 of the new
+    /// PID namespace, reaping the orphans and reporting the exit status of the
+    /// program, which is mirrored by the spawned process.
+    fn unshare_ns(&mut self, flags: nix::sched::CloneFlags, uid: u32, gid: u32) -> &mut Command;
+
     /// Chroot to a new path before exec.
     fn chroot<P: AsRef<Path>>(&mut self, new_root: P) -> &mut Command;
 
     fn with_proc(&mut self) -> &mut Command;
+
+    /// Mount an overlay filesystem on `target` before exec, whose upper directory is `target`.
+    ///
+    /// The mount is private to the mount namespace of the program, so it does not
+    /// require root with a user namespace. Files written by the program are kept
+    /// in `target` while `lower` is left unchanged.
+    fn overlay<P: AsRef<Path>>(&mut self, target: P, lower: P, work: P) -> &mut Command;
+
+    /// Bind `source` to `target` read-only before exec, which must be in a new mount namespace.
+    ///
+    /// The flags of the filesystem of `source` such as `nosuid` are kept, since they can
+    /// not be cleared in a user namespace.
+    fn bind_read_only<P: AsRef<Path>>(&mut self, source: P, target: P) -> io::Result<&mut Command>;
+
+    /// Limit the max size of files the program writes with `RLIMIT_FSIZE`.
+    fn file_size_limit(&mut self, size: u64) -> &mut Command;
+
+    /// Forbid the program to gain new privileges by executing other programs.
+    fn no_new_privs(&mut self) -> &mut Command;
+
+    /// Run the program as `uid` and `gid` without supplementary groups, which requires root.
+    fn switch_user(&mut self, uid: u32, gid: u32) -> &mut Command;
 }
 
 impl CommandExt for Command {
     fn unshare_all_ns(&mut self) -> &mut Command {
+        self.unshare_ns(
+            nix::sched::CloneFlags::empty()
+                | nix::sched::CloneFlags::CLONE_FILES
+                | nix::sched::CloneFlags::CLONE_NEWCGROUP
+                | nix::sched::CloneFlags::CLONE_NEWIPC
+                | nix::sched::CloneFlags::CLONE_NEWNET
+                | nix::sched::CloneFlags::CLONE_NEWNS
+                | nix::sched::CloneFlags::CLONE_NEWPID
+                | nix::sched::CloneFlags::CLONE_NEWUSER
+                | nix::sched::CloneFlags::CLONE_NEWUTS,
+            NOBODY,
+            NOBODY,
+        )
+    }
+
+    fn unshare_ns(&mut self, flags: nix::sched::CloneFlags, uid: u32, gid: u32) -> &mut Command {
+        use nix::sched::CloneFlags;
+
+        // Everything used after fork is prepared here to avoid allocation in the child.
+        let uid_map = format!("0 {} 1", uid);
+        let gid_map = format!("0 {} 1", gid);
         unsafe {
             self.pre_exec(move || {
-                nix::sched::unshare(
-                    nix::sched::CloneFlags::empty()
-                        | nix::sched::CloneFlags::CLONE_FILES
-                        | nix::sched::CloneFlags::CLONE_NEWIPC
-                        | nix::sched::CloneFlags::CLONE_NEWNET
-                        | nix::sched::CloneFlags::CLONE_NEWNS
-                        // | nix::sched::CloneFlags::CLONE_NEWPID
-                        // | nix::sched::CloneFlags::CLONE_NEWUSER
-                        | nix::sched::CloneFlags::CLONE_NEWUTS,
-                )
-                .expect("Failed to unshare namespace");
+                if flags.contains(CloneFlags::CLONE_NEWUSER) {
+                    enter_user_ns(uid, gid, &uid_map, &gid_map)?;
+                }
+                if libc::unshare((flags - CloneFlags::CLONE_NEWUSER).bits()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                if flags.contains(CloneFlags::CLONE_NEWPID) {
+                    // Only the program returns here, the other processes exit in it.
+                    spawn_init()?;
+                }
                 Ok(())
             });
         }
@@ -77,6 +134,295 @@ impl CommandExt for Command {
         }
         self
     }
+
+    fn overlay<P: AsRef<Path>>(&mut self, target: P, lower: P, work: P) -> &mut Command {
+        let target = target.as_ref().to_owned();
+        let data = format!(
+            "lowerdir={},upperdir={},workdir={}",
+            lower.as_ref().display(),
+            target.display(),
+            work.as_ref().display()
+        );
+        unsafe {
+            self.pre_exec(move || {
+                nix::mount::mount(
+                    Some("overlay"),
+                    &target,
+                    Some("overlay"),
+                    nix::mount::MsFlags::empty(),
+                    Some(data.as_str()),
+                )
+                .map_err(|_| io::Error::other("mount runtime overlay failed!"))?;
+                Ok(())
+            });
+        }
+        self
+    }
+
+    fn bind_read_only<P: AsRef<Path>>(&mut self, source: P, target: P) -> io::Result<&mut Command> {
+        use nix::mount::MsFlags;
+        use nix::sys::statvfs::{statvfs, FsFlags};
+
+        let source = source.as_ref().to_owned();
+        let target = target.as_ref().to_owned();
+        let fs_flags = statvfs(&source)
+            .map_err(|e| io::Error::other(format!("Failed to bind {}: {}", source.display(), e)))?
+            .flags();
+        let mut flags = MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY;
+        for (fs_flag, flag) in [
+            (FsFlags::ST_NOSUID, MsFlags::MS_NOSUID),
+            (FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+            (FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+            (FsFlags::ST_NOATIME, MsFlags::MS_NOATIME),
+            (FsFlags::ST_NODIRATIME, MsFlags::MS_NODIRATIME),
+            (FsFlags::ST_RELATIME, MsFlags::MS_RELATIME),
+        ]
+        .iter()
+        {
+            if fs_flags.contains(*fs_flag) {
+                flags |= *flag;
+            }
+        }
+        unsafe {
+            self.pre_exec(move || {
+                let none = Option::<&str>::None;
+                // the mounts must not be propagated to the namespace of Ana
+                nix::mount::mount(none, "/", none, MsFlags::MS_REC | MsFlags::MS_PRIVATE, none)
+                    .and_then(|_| {
+                        nix::mount::mount(
+                            Some(&source),
+                            &target,
+                            none,
+                            MsFlags::MS_BIND | MsFlags::MS_REC,
+                            none,
+                        )
+                    })
+                    .and_then(|_| nix::mount::mount(none, &target, none, flags, none))
+                    .map_err(|_| io::Error::other("bind mount failed!"))?;
+                Ok(())
+            });
+        }
+        Ok(self)
+    }
+
+    fn file_size_limit(&mut self, size: u64) -> &mut Command {
+        unsafe {
+            self.pre_exec(move || {
+                let limit = nix::libc::rlimit {
+                    rlim_cur: size,
+                    rlim_max: size,
+                };
+                if nix::libc::setrlimit(nix::libc::RLIMIT_FSIZE, &limit) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        self
+    }
+
+    fn no_new_privs(&mut self) -> &mut Command {
+        unsafe {
+            self.pre_exec(move || {
+                if nix::libc::prctl(nix::libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        self
+    }
+
+    fn switch_user(&mut self, uid: u32, gid: u32) -> &mut Command {
+        unsafe {
+            self.pre_exec(move || {
+                if libc::setgroups(0, std::ptr::null()) != 0
+                    || libc::setresgid(gid, gid, gid) != 0
+                    || libc::setresuid(uid, uid, uid) != 0
+                    // changing the credentials makes the process undumpable and `/proc/self` inaccessible
+                    || libc::prctl(libc::PR_SET_DUMPABLE, 1, 0, 0, 0) != 0
+                {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        self
+    }
+}
+
+/// The overflow uid and gid.
+const NOBODY: u32 = 65534;
+
+/// Whether Ana runs without root, which requires a user namespace for the program.
+pub fn rootless() -> bool {
+    !nix::unistd::geteuid().is_root()
+}
+
+/// Enter a new user namespace in which root is mapped to `uid` and `gid`.
+///
+/// Privileges of root are dropped before unsharing, so the new namespace is owned by
+/// the unprivileged user rather than root outside. Without root, `uid` and `gid` must
+/// be the ones of the current process.
+unsafe fn enter_user_ns(uid: u32, gid: u32, uid_map: &str, gid_map: &str) -> io::Result<()> {
+    if libc::geteuid() == 0
+        && (libc::setgroups(0, std::ptr::null()) != 0
+            || libc::setresgid(gid, gid, gid) != 0
+            || libc::setresuid(uid, uid, uid) != 0
+            // changing the credentials makes the process undumpable and `/proc/self` inaccessible
+            || libc::prctl(libc::PR_SET_DUMPABLE, 1, 0, 0, 0) != 0)
+    {
+        return Err(io::Error::last_os_error());
+    }
+    if libc::unshare(libc::CLONE_NEWUSER) != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    write_proc_file(b"/proc/self/setgroups\0", b"deny")?;
+    write_proc_file(b"/proc/self/uid_map\0", uid_map.as_bytes())?;
+    write_proc_file(b"/proc/self/gid_map\0", gid_map.as_bytes())?;
+    Ok(())
+}
+
+/// Write `content` to a file under `/proc` with raw system calls only.
+///
+/// `path` must be nul-terminated.
+unsafe fn write_proc_file(path: &[u8], content: &[u8]) -> io::Result<()> {
+    let fd = libc::open(
+        path.as_ptr() as *const libc::c_char,
+        libc::O_WRONLY | libc::O_CLOEXEC,
+    );
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let written = libc::write(fd, content.as_ptr() as *const libc::c_void, content.len());
+    let err = io::Error::last_os_error();
+    libc::close(fd);
+    if written != content.len() as isize {
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Fork an init process into the new PID namespace, which forks the program in turn.
+///
+/// This function only returns in the program. The calling process waits for the init
+/// process and exits with the status of the program, and the init process reaps
+/// all processes in the namespace until the program exits.
+///
+/// All file descriptors of both processes are closed, so that the pipes of the
+/// program are not held open by them.
+unsafe fn spawn_init() -> io::Result<()> {
+    let mut status_pipe = [0; 2];
+    if libc::pipe2(status_pipe.as_mut_ptr(), libc::O_CLOEXEC) != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let [status_reader, status_writer] = status_pipe;
+
+    match libc::fork() {
+        -1 => return Err(io::Error::last_os_error()),
+        0 => {}
+        init => {
+            close_fds_except(status_reader);
+            let mut status: libc::c_int = 0;
+            let mut received = 0;
+            while received < std::mem::size_of::<libc::c_int>() {
+                let n = libc::read(
+                    status_reader,
+                    (&mut status as *mut libc::c_int as *mut u8).add(received) as *mut libc::c_void,
+                    std::mem::size_of::<libc::c_int>() - received,
+                );
+                match n {
+                    n if n > 0 => received += n as usize,
+                    -1 if *libc::__errno_location() == libc::EINTR => continue,
+                    _ => break,
+                }
+            }
+            let mut init_status: libc::c_int = 0;
+            while libc::waitpid(init, &mut init_status, 0) == -1
+                && *libc::__errno_location() == libc::EINTR
+            {}
+            if received < std::mem::size_of::<libc::c_int>() {
+                // The init process died before the program exits
+                status = init_status;
+            }
+            exit_with_status(status);
+        }
+    }
+
+    // init process
+    libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL, 0, 0, 0);
+    libc::close(status_reader);
+    let program = match libc::fork() {
+        -1 => libc::_exit(1),
+        0 => {
+            libc::close(status_writer);
+            return Ok(());
+        }
+        program => program,
+    };
+    close_fds_except(status_writer);
+    loop {
+        let mut status: libc::c_int = 0;
+        let pid = libc::waitpid(-1, &mut status, 0);
+        if pid == program {
+            libc::write(
+                status_writer,
+                &status as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>(),
+            );
+            libc::_exit(0);
+        }
+        if pid == -1 && *libc::__errno_location() != libc::EINTR {
+            libc::_exit(1);
+        }
+    }
+}
+
+/// Close all file descriptors except `keep`.
+unsafe fn close_fds_except(keep: libc::c_int) {
+    if keep > 0 {
+        close_fds(0, keep as libc::c_uint - 1);
+    }
+    close_fds(keep as libc::c_uint + 1, libc::c_uint::MAX);
+}
+
+/// Close the file descriptors from `first` to `last` inclusively.
+unsafe fn close_fds(first: libc::c_uint, last: libc::c_uint) {
+    if libc::syscall(libc::SYS_close_range, first, last, 0) == 0 {
+        return;
+    }
+    // close_range is not available before Linux 5.9
+    let max_fd = match libc::sysconf(libc::_SC_OPEN_MAX) {
+        n if n < 0 => 1024,
+        n => n as libc::c_uint,
+    };
+    let last = last.min(max_fd);
+    for fd in first..=last {
+        libc::close(fd as libc::c_int);
+    }
+}
+
+/// Exit in the same way as a process with the wait `status`.
+unsafe fn exit_with_status(status: libc::c_int) -> ! {
+    if libc::WIFSIGNALED(status) {
+        let signal = libc::WTERMSIG(status);
+        let no_core = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        libc::setrlimit(libc::RLIMIT_CORE, &no_core);
+        libc::signal(signal, libc::SIG_DFL);
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, signal);
+        libc::sigprocmask(libc::SIG_UNBLOCK, &set, std::ptr::null_mut());
+        libc::raise(signal);
+        libc::_exit(128 + signal);
+    }
+    if libc::WIFEXITED(status) {
+        libc::_exit(libc::WEXITSTATUS(status));
+    }
+    libc::_exit(1)
 }
 
 /// Extra features for child process.
@@ -1,14 +1,15 @@
 //! High-level APIs for libseccomp.
 mod libseccomp;
+mod profile;
 use libseccomp::*;
 
+pub use profile::{DEFAULT_WHITELIST, JVM_WHITELIST};
+
 use std::ffi::CString;
 use std::ops::Deref;
 use std::os::unix::process::CommandExt as _;
 use std::process::Command;
 
-use nix;
-
 /// Syscall wrapper.
 pub struct Syscall(u32);
 
@@ -17,10 +18,19 @@ impl Syscall {
     ///
     /// Panic if the argument is not a available syscall name.
     pub fn from_name(name: &str) -> Syscall {
-        let name = CString::new(name).unwrap();
+        Syscall::resolve(name).expect("No such syscall")
+    }
+
+    /// Resolve the name of a syscall.
+    ///
+    /// Return `None` if the syscall is not available on this architecture.
+    pub fn resolve(name: &str) -> Option<Syscall> {
+        let name = CString::new(name).ok()?;
         let syscall = unsafe { seccomp_syscall_resolve_name(name.as_ptr()) };
-        assert!(syscall >= 0, "No such syscall");
-        Syscall(syscall as u32)
+        if syscall < 0 {
+            return None;
+        }
+        Some(Syscall(syscall as u32))
     }
 }
 
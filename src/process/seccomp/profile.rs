@@ -0,0 +1,92 @@
+//! Built-in whitelists of system calls.
+
+/// System calls used by common C/C++ programs.
+///
+/// The signal system calls are used by `abort`, so a failed `assert` is a runtime error.
+pub const DEFAULT_WHITELIST: &[&str] = &[
+    "access",
+    "arch_prctl",
+    "brk",
+    "clock_getres",
+    "clock_gettime",
+    "clock_nanosleep",
+    "close",
+    "dup",
+    "dup2",
+    "dup3",
+    "execve",
+    "exit",
+    "exit_group",
+    "faccessat",
+    "faccessat2",
+    "fcntl",
+    "fstat",
+    "futex",
+    "getegid",
+    "geteuid",
+    "getgid",
+    "getpid",
+    "getrandom",
+    "getrusage",
+    "gettid",
+    "gettimeofday",
+    "getuid",
+    "ioctl",
+    "kill",
+    "lseek",
+    "lstat",
+    "madvise",
+    "mmap",
+    "mprotect",
+    "mremap",
+    "munmap",
+    "nanosleep",
+    "newfstatat",
+    "open",
+    "openat",
+    "pread64",
+    "prlimit64",
+    "read",
+    "readlink",
+    "readlinkat",
+    "readv",
+    "rseq",
+    "rt_sigaction",
+    "rt_sigprocmask",
+    "rt_sigreturn",
+    "sched_yield",
+    "set_robust_list",
+    "set_tid_address",
+    "sigaltstack",
+    "stat",
+    "statx",
+    "sysinfo",
+    "tgkill",
+    "time",
+    "times",
+    "tkill",
+    "uname",
+    "write",
+    "writev",
+];
+
+/// System calls used by JVM and other multi-threaded runtimes
+/// besides the ones in `DEFAULT_WHITELIST`.
+pub const JVM_WHITELIST: &[&str] = &[
+    "clone",
+    "clone3",
+    "getcwd",
+    "getdents64",
+    "getppid",
+    "membarrier",
+    "pipe",
+    "pipe2",
+    "poll",
+    "ppoll",
+    "prctl",
+    "sched_getaffinity",
+    "sched_getparam",
+    "sched_getscheduler",
+    "sched_setaffinity",
+    "wait4",
+];
@@ -7,7 +7,6 @@ use std::time::Duration;
 use std::time::Instant;
 
 use cgroup::CommandExt as _;
-use tempfile;
 
 const PROGRAM: &str = r#"/bin/sh"#;
 const TIMEOUT_INPUT_CONTENT: &str = r#"sleep 100"#;
@@ -73,7 +72,7 @@ async fn test_cgroup() -> io::Result<()> {
     let cg_ctx = cg_ctx!();
 
     let start_time = Instant::now();
-    let exit_status = Command::new(&PROGRAM)
+    let exit_status = Command::new(PROGRAM)
         .stdin(File::open(&input_file)?)
         .stdout(File::create(&output_file)?)
         .cgroup(cg_ctx.clone())
@@ -102,12 +101,14 @@ fn test_chroot() -> io::Result<()> {
     let input_file = input_file!();
     let output_file = output_file!();
 
-    let exit_status = Command::new(&PROGRAM)
-        .stdin(File::open(&input_file)?)
-        .stdout(File::create(&output_file)?)
-        .chroot("/")
-        .spawn()?
-        .wait()?;
+    let exit_status = CommandExt::chroot(
+        Command::new(PROGRAM)
+            .stdin(File::open(&input_file)?)
+            .stdout(File::create(&output_file)?),
+        "/",
+    )
+    .spawn()?
+    .wait()?;
 
     assert!(exit_status.success());
     assert_eq!(fs::read(&output_file)?, ANSWER_CONTENT.as_bytes());
@@ -120,7 +121,7 @@ fn test_unshare() -> io::Result<()> {
     let input_file = input_file!();
     let output_file = output_file!();
 
-    let exit_status = Command::new(&PROGRAM)
+    let exit_status = Command::new(PROGRAM)
         .stdin(File::open(&input_file)?)
         .stdout(File::create(&output_file)?)
         .unshare_all_ns()
@@ -133,12 +134,99 @@ fn test_unshare() -> io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_pid_namespace() -> io::Result<()> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let output = Command::new(PROGRAM)
+        .arg("-c")
+        .arg("echo $$; exit 3")
+        .unshare_ns(nix::sched::CloneFlags::CLONE_NEWPID, 0, 0)
+        .output()?;
+    assert_eq!(output.stdout, b"2\n");
+    assert_eq!(output.status.code(), Some(3));
+
+    let status = Command::new(PROGRAM)
+        .arg("-c")
+        .arg("kill -9 $$")
+        .unshare_ns(nix::sched::CloneFlags::CLONE_NEWPID, 0, 0)
+        .status()?;
+    assert_eq!(status.signal(), Some(nix::libc::SIGKILL));
+
+    Ok(())
+}
+
+#[test]
+fn test_user_namespace() -> io::Result<()> {
+    let output = Command::new(PROGRAM)
+        .arg("-c")
+        .arg("id -u; cat /proc/self/uid_map")
+        .unshare_ns(nix::sched::CloneFlags::CLONE_NEWUSER, 65534, 65534)
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout.split_whitespace().collect::<Vec<_>>(),
+        ["0", "0", "65534", "1"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_overlay() -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    // the overlay is mounted by nobody in a user namespace as it is in rootless mode
+    let dir = tempfile::tempdir()?;
+    let target = dir.path().join("target");
+    let lower = dir.path().join("lower");
+    let work = dir.path().join("work");
+    for path in [dir.path(), &target, &lower, &work].iter() {
+        fs::create_dir_all(path)?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o777))?;
+    }
+    fs::write(lower.join("base"), ANSWER_CONTENT)?;
+
+    let output = Command::new(PROGRAM)
+        .arg("-c")
+        .arg(format!("cat {0}/base && touch {0}/new", target.display()))
+        .unshare_ns(
+            nix::sched::CloneFlags::CLONE_NEWUSER | nix::sched::CloneFlags::CLONE_NEWNS,
+            65534,
+            65534,
+        )
+        .overlay(&target, &lower, &work)
+        .output()?;
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, ANSWER_CONTENT.as_bytes());
+    assert!(target.join("new").exists());
+    assert!(!target.join("base").exists());
+    assert!(!lower.join("new").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_features() {
+    let features = features::Features::detect();
+
+    assert!(!features.rootless);
+    assert!(features.user_namespace);
+    assert!(features.overlay);
+    assert!(features.cpuset_cpus.is_some());
+    assert!(features.missing_controllers().is_empty());
+    assert!(features.is_sufficient());
+}
+
 #[test]
 fn test_timeout() -> io::Result<()> {
     let input_file = timeout_input_file!();
     let output_file = output_file!();
 
-    let exit_status = Command::new(&PROGRAM)
+    let exit_status = Command::new(PROGRAM)
         .stdin(File::open(&input_file)?)
         .stdout(File::create(&output_file)?)
         .spawn()?
@@ -151,14 +239,51 @@ fn test_timeout() -> io::Result<()> {
 
 #[cfg(feature = "seccomp")]
 #[test]
-#[should_panic]
-fn test_seccomp() {
-    unimplemented!("TODO: Add seccomp test")
+fn test_seccomp() -> io::Result<()> {
+    use std::os::unix::process::ExitStatusExt;
+
+    use seccomp::CommandExt as _;
+
+    // only the uname system call is filtered
+    let mut ctx = seccomp::Context::new(seccomp::Act::Allow);
+    ctx.add_rule(seccomp::Rule::blacklist(seccomp::Syscall::from_name(
+        "uname",
+    )))
+    .map_err(io::Error::other)?;
+    let exit_status = Command::new("uname").seccomp(ctx).status()?;
+
+    assert_eq!(exit_status.signal(), Some(nix::libc::SIGSYS));
+
+    Ok(())
+}
+
+#[test]
+fn test_no_new_privs() -> io::Result<()> {
+    let output = Command::new(PROGRAM)
+        .arg("-c")
+        .arg("grep NoNewPrivs /proc/self/status")
+        .no_new_privs()
+        .output()?;
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"NoNewPrivs:\t1\n");
+
+    Ok(())
 }
 
 #[cfg(feature = "cap-ng")]
 #[test]
-#[should_panic]
-fn test_capabilities() {
-    unimplemented!("TODO: Add capabilities test")
+fn test_capabilities() -> io::Result<()> {
+    use capng::CommandExt as _;
+
+    let output = Command::new(PROGRAM)
+        .arg("-c")
+        .arg("grep CapEff /proc/self/status")
+        .capabilities(vec![])
+        .output()?;
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"CapEff:\t0000000000000000\n");
+
+    Ok(())
 }
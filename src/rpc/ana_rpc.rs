@@ -1,6 +1,8 @@
+use std::io;
 use std::time::Duration;
 
 use crate::judge;
+use crate::language;
 use crate::workspace;
 
 tonic::include_proto!("ana_rpc");
@@ -11,6 +13,11 @@ impl From<judge::Report> for Report {
             result: self::report::ResultType::from(report.result) as i32,
             usage: report.usage.map(Resource::from),
             message: Some(report.message),
+            interactor_usage: report.interactor_usage.map(Resource::from),
+            score: report.score.map(Score::from),
+            summary: report.summary,
+            case: report.case.map(|case| case as u64),
+            subtask: report.subtask.map(|subtask| subtask as u64),
         }
     }
 }
@@ -23,9 +30,12 @@ impl From<judge::ResultType> for report::ResultType {
             WrongAnswer => report::ResultType::WrongAnswer,
             TimeLimitExceeded => report::ResultType::TimeLimitExceeded,
             MemoryLimitExceeded => report::ResultType::MemoryLimitExceeded,
+            OutputLimitExceeded => report::ResultType::OutputLimitExceeded,
+            RestrictedFunction => report::ResultType::RestrictedFunction,
             RuntimeError => report::ResultType::RuntimeError,
             CompileError => report::ResultType::CompileError,
             SystemError => report::ResultType::SystemError,
+            Cancelled => report::ResultType::Cancelled,
         }
     }
 }
@@ -42,6 +52,16 @@ impl From<judge::Resource> for Resource {
     }
 }
 
+impl From<judge::Score> for Score {
+    fn from(score: judge::Score) -> Score {
+        Score {
+            subtask: score.subtask.map(|subtask| subtask as u64),
+            score: score.score,
+            full_score: score.full_score,
+        }
+    }
+}
+
 impl From<Resource> for workspace::problem::ResourceLimit {
     fn from(resource: Resource) -> workspace::problem::ResourceLimit {
         let real_time = match resource.real_time {
@@ -56,6 +76,21 @@ impl From<Resource> for workspace::problem::ResourceLimit {
             real_time,
             cpu_time,
             memory: resource.memory.unwrap_or(0) as usize,
+            output: None,
+            processes: None,
         }
     }
 }
+
+impl Language {
+    /// Describe the language, running its toolchain for the version.
+    pub fn from_language(language: &language::Language) -> io::Result<Language> {
+        Ok(Language {
+            id: language.name(),
+            extension: language.extension(),
+            default: language.is_default(),
+            version: language.version(),
+            missing_tools: language.missing_tools()?,
+        })
+    }
+}
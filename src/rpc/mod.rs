@@ -1,73 +1,43 @@
 mod ana_rpc;
+mod queue;
+mod register;
+mod task;
 
-use std::collections::HashMap;
+use std::io;
 use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
 use std::process;
+use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use futures::executor;
 use futures::prelude::*;
-use lazy_static::lazy_static;
 use tokio::runtime::{self, Runtime};
-use tokio::sync::mpsc::{self, UnboundedReceiver};
-use tokio::sync::RwLock;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tonic::transport::Server;
 use tonic::{Request, Response, Status};
 
 use crate::judge;
-use crate::workspace::Workspace;
+use crate::language::Language;
+use crate::workspace::{ProblemStore, Workspace};
 use ana_rpc as rpc;
+use queue::Queue;
+use register::Register;
 
-lazy_static! {
-    static ref REGISTER: Register = Register::new();
-}
-
-struct Register {
-    register: RwLock<HashMap<String, UnboundedReceiver<rpc::Report>>>,
-}
-
-impl Register {
-    fn new() -> Register {
-        Register {
-            register: RwLock::new(HashMap::new()),
-        }
-    }
-
-    async fn register<T: Stream<Item = rpc::Report> + Send + 'static>(
-        &self,
-        id: String,
-        reports: T,
-    ) {
-        let (tx, rx) = mpsc::unbounded_channel();
-        tokio::spawn(reports.for_each(move |report| {
-            let _ = tx.send(report);
-            future::ready(())
-        }));
-        let mut register = self.register.write().await;
-        if register.contains_key(&id) {
-            log::warn!("the task ID {} is exist. The new one is ignored.", &id);
-            return;
-        }
-        register.insert(id.clone(), rx);
-    }
+/// Time of keeping the finished tasks by default.
+const DEFAULT_TASK_TTL: Duration = Duration::from_secs(600);
 
-    async fn get_report(&self, id: &str) -> Option<rpc::Report> {
-        let mut rx = {
-            let mut register = self.register.write().await;
-            register.remove(id)?
-        };
-        let res = rx.recv().await;
-        if res.is_some() {
-            let mut register = self.register.write().await;
-            register.insert(id.to_string(), rx);
-        }
-        res
-    }
-}
+/// Max number of the waiting tasks by default.
+const DEFAULT_QUEUE_SIZE: usize = 1024;
 
 pub struct RpcServer {
     runtime: Runtime,
+    problem_store: Option<ProblemStore>,
+    trust_clients: bool,
+    register: Arc<Register>,
+    queue: Arc<Queue>,
 }
 
 impl RpcServer {
@@ -77,7 +47,44 @@ impl RpcServer {
             .enable_all()
             .build()
             .expect("Failed to create a runtime");
-        RpcServer { runtime }
+        RpcServer {
+            runtime,
+            problem_store: None,
+            trust_clients: false,
+            register: Arc::new(Register::new(DEFAULT_TASK_TTL)),
+            queue: Arc::new(Queue::new(max_threads, DEFAULT_QUEUE_SIZE)),
+        }
+    }
+
+    /// Judge at most `max_judges` tasks at the same time, keeping at most
+    /// `queue_size` tasks waiting.
+    ///
+    /// The max number of judges is the number of threads by default.
+    pub fn queue(mut self, max_judges: usize, queue_size: usize) -> RpcServer {
+        self.queue = Arc::new(Queue::new(max_judges, queue_size));
+        self
+    }
+
+    /// Keep the status and the reports of the finished tasks for `ttl`.
+    pub fn task_ttl(mut self, ttl: Duration) -> RpcServer {
+        self.register = Arc::new(Register::new(ttl));
+        self
+    }
+
+    /// Cache the problems in the store, which the tasks can reference by id.
+    pub fn problem_store(mut self, store: ProblemStore) -> RpcServer {
+        self.problem_store = Some(store);
+        self
+    }
+
+    /// Accept the build scripts, the runner config and the extern programs of
+    /// the inline problems of the tasks, and the problems to cache.
+    ///
+    /// The build script runs outside the sandbox as the user of Ana, so the clients are
+    /// fully trusted with it.
+    pub fn trust_clients(mut self) -> RpcServer {
+        self.trust_clients = true;
+        self
     }
 
     pub fn start(self, address: IpAddr, port: u16) -> ! {
@@ -93,8 +100,132 @@ impl RpcServer {
     }
 }
 
+impl RpcServer {
+    /// Judge the workspace in the runtime after it is scheduled by the queue,
+    /// sending the reports to `tx`.
+    ///
+    /// `reports` is the receiver of `tx` if the reports are polled by `GetReport`.
+    /// `holder` is dropped after judging, which keeps the resources of the workspace.
+    /// The last report is a `Cancelled` summary if the task is cancelled.
+    async fn spawn_judge<T: Send + 'static>(
+        &self,
+        id: String,
+        priority: i32,
+        workspace: Workspace,
+        holder: T,
+        tx: UnboundedSender<judge::Report>,
+        reports: Option<UnboundedReceiver<judge::Report>>,
+    ) -> Result<(), Status> {
+        let priority = match rpc::Priority::from_i32(priority) {
+            Some(priority) => priority,
+            None => return Err(Status::invalid_argument("priority of task is invalid")),
+        };
+        let cancellation = match self.register.start(&id, reports).await {
+            Some(cancellation) => cancellation,
+            None => {
+                return Err(Status::already_exists(format!(
+                    "the task {} is not finished",
+                    id
+                )))
+            }
+        };
+        let permit = match self.queue.push(&id, priority) {
+            Some(permit) => permit,
+            None => {
+                self.register.remove(&id).await;
+                return Err(Status::resource_exhausted("the judge queue is full"));
+            }
+        };
+        let register = self.register.clone();
+        self.runtime.spawn(async move {
+            let _holder = holder;
+            let res = match permit.await {
+                Ok(_permit) => {
+                    register.run(&id).await;
+                    judge::judge_cancellable(workspace, tx.clone(), &cancellation).await
+                }
+                // the task is removed from the queue when it is cancelled
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    "The task is removed from the queue",
+                )),
+            };
+            let report = match res {
+                Ok(()) => None,
+                Err(_) if cancellation.is_cancelled() => Some(judge::Report::new(
+                    judge::ResultType::Cancelled,
+                    None,
+                    String::from("The task is cancelled"),
+                )),
+                Err(e) => Some(judge::Report::new(
+                    judge::ResultType::SystemError,
+                    None,
+                    format!("Failed to judge task. {}", e),
+                )),
+            };
+            if let Some(report) = report {
+                let _ = tx.send(report.into_summary());
+            }
+            register.finish(&id).await;
+        });
+        Ok(())
+    }
+
+    /// Fill the position of the queued task in its status.
+    fn queued_status(&self, mut status: rpc::TaskStatus) -> rpc::TaskStatus {
+        if let Some(id) = status.id.as_ref() {
+            status.queue_position = self.queue.position(id).map(|position| position as u64);
+        }
+        status
+    }
+}
+
 #[async_trait]
 impl rpc::ana_server::Ana for RpcServer {
+    type JudgeStream =
+        Pin<Box<dyn Stream<Item = Result<rpc::Report, Status>> + Send + Sync + 'static>>;
+
+    async fn judge(
+        &self,
+        request: Request<rpc::Task>,
+    ) -> Result<Response<Self::JudgeStream>, Status> {
+        let task = request.into_inner();
+        let priority = task.priority;
+        let id = match task.id.clone() {
+            Some(id) => id,
+            None => return Err(Status::data_loss("id of task is missing")),
+        };
+        if !self.trust_clients {
+            task::check_untrusted(&task).map_err(|e| io_status("the task is rejected", e))?;
+        }
+        let (dir, workspace) = task::create_workspace(task, self.problem_store.as_ref())
+            .await
+            .map_err(|e| io_status("failed to create workspace", e))?;
+        log::debug!("Judge task {} in {}", id, dir.path().display());
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.spawn_judge(id, priority, workspace, dir, tx, None)
+            .await?;
+        let reports = UnboundedReceiverStream::new(rx).map(streamed_report);
+        Ok(Response::new(Box::pin(reports)))
+    }
+
+    async fn cache(&self, request: Request<rpc::Problem>) -> Result<Response<()>, Status> {
+        // the cached problems are used by all tasks, including their extern programs
+        if !self.trust_clients {
+            return Err(Status::permission_denied(
+                "caching problems requires trusted clients",
+            ));
+        }
+        let store = match self.problem_store.as_ref() {
+            Some(store) => store,
+            None => return Err(Status::failed_precondition("problem store is disabled")),
+        };
+        task::cache_problem(request.into_inner(), store)
+            .await
+            .map_err(|e| io_status("failed to cache problem", e))?;
+        Ok(Response::new(()))
+    }
+
     async fn judge_workspace(
         &self,
         request: Request<rpc::Workspace>,
@@ -118,23 +249,41 @@ impl rpc::ana_server::Ana for RpcServer {
             }
         };
         let (tx, rx) = mpsc::unbounded_channel();
-        let tmp_id = id.clone();
-        self.runtime.spawn(async move {
-            if let Err(e) = judge::judge(workspace, tx.clone()).await {
-                let _ = tx.send(judge::Report {
-                    result: judge::ResultType::SystemError,
-                    usage: None,
-                    message: format!("Failed to judge task. {}", e),
-                });
-            }
-        });
-
-        REGISTER
-            .register(id, UnboundedReceiverStream::new(rx).map(rpc::Report::from))
-            .await;
+        self.spawn_judge(id, request.priority, workspace, (), tx, Some(rx))
+            .await?;
         Ok(Response::new(()))
     }
 
+    async fn cancel(&self, request: Request<rpc::Request>) -> Result<Response<()>, Status> {
+        let id = match request.into_inner().id {
+            Some(id) => id,
+            None => return Err(Status::data_loss("id of task is missing")),
+        };
+        if self.register.cancel(&id).await {
+            self.queue.remove(&id);
+            Ok(Response::new(()))
+        } else {
+            Err(Status::not_found("the task is not running"))
+        }
+    }
+
+    async fn list_languages(
+        &self,
+        _request: Request<()>,
+    ) -> Result<Response<rpc::LanguageList>, Status> {
+        // the toolchains are executed for their versions
+        let languages = tokio::task::spawn_blocking(|| {
+            Language::all()?
+                .iter()
+                .map(rpc::Language::from_language)
+                .collect::<io::Result<_>>()
+        })
+        .await
+        .map_err(|e| Status::internal(format!("failed to list languages. {}", e)))?
+        .map_err(|e| io_status("failed to list languages", e))?;
+        Ok(Response::new(rpc::LanguageList { languages }))
+    }
+
     async fn get_report(
         &self,
         request: Request<rpc::Request>,
@@ -143,11 +292,49 @@ impl rpc::ana_server::Ana for RpcServer {
             Some(id) => id,
             None => return Err(Status::data_loss("id of task is missing")),
         };
-        match REGISTER.get_report(&id).await {
-            Some(report) => Ok(Response::new(report)),
-            None => Err(Status::out_of_range(
-                "the task is finished or does not exist",
-            )),
+        self.register.get_report(&id).await.map(Response::new)
+    }
+
+    async fn get_status(
+        &self,
+        request: Request<rpc::Request>,
+    ) -> Result<Response<rpc::TaskStatus>, Status> {
+        let id = match request.into_inner().id {
+            Some(id) => id,
+            None => return Err(Status::data_loss("id of task is missing")),
+        };
+        match self.register.status(&id).await {
+            Some(status) => Ok(Response::new(self.queued_status(status))),
+            None => Err(Status::not_found("the task does not exist")),
         }
     }
+
+    async fn list_tasks(&self, _request: Request<()>) -> Result<Response<rpc::TaskList>, Status> {
+        let tasks = self.register.list().await;
+        let tasks = tasks
+            .into_iter()
+            .map(|status| self.queued_status(status))
+            .collect();
+        Ok(Response::new(rpc::TaskList { tasks }))
+    }
 }
+
+/// Convert an error of handling the request to a status.
+fn io_status(message: &str, e: io::Error) -> Status {
+    let message = format!("{}. {}", message, e);
+    match e.kind() {
+        io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => {
+            Status::invalid_argument(message)
+        }
+        io::ErrorKind::NotFound => Status::not_found(message),
+        _ => Status::internal(message),
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn streamed_report(report: judge::Report) -> Result<rpc::Report, Status> {
+    Ok(rpc::Report::from(report))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,130 @@
+//! Queue of the tasks waiting for judging, which limits the running tasks.
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::oneshot;
+
+use super::ana_rpc as rpc;
+
+/// Queue judging at most `max_running` tasks at the same time.
+///
+/// The waiting tasks are ordered by priority and then by the time of pushing.
+pub struct Queue {
+    state: Mutex<State>,
+    max_running: usize,
+    capacity: usize,
+}
+
+struct State {
+    running: usize,
+    waiting: Vec<Waiting>,
+}
+
+struct Waiting {
+    id: String,
+    rank: u8,
+    tx: oneshot::Sender<Permit>,
+}
+
+/// Permission of judging a task, which frees its slot when dropped.
+pub struct Permit {
+    queue: Option<Arc<Queue>>,
+}
+
+impl Queue {
+    pub fn new(max_running: usize, capacity: usize) -> Queue {
+        Queue {
+            state: Mutex::new(State {
+                running: 0,
+                waiting: Vec::new(),
+            }),
+            max_running,
+            capacity,
+        }
+    }
+
+    /// Push the task into the queue, returning the receiver of its permit.
+    ///
+    /// Return `None` if there is no free slot and `capacity` tasks are waiting.
+    /// The receiver fails if the task is removed from the queue.
+    pub fn push(
+        self: &Arc<Self>,
+        id: &str,
+        priority: rpc::Priority,
+    ) -> Option<oneshot::Receiver<Permit>> {
+        let mut state = self.state.lock().unwrap();
+        if state.running >= self.max_running && state.waiting.len() >= self.capacity {
+            return None;
+        }
+        let (tx, rx) = oneshot::channel();
+        let rank = rank(priority);
+        let index = state
+            .waiting
+            .iter()
+            .position(|waiting| waiting.rank < rank)
+            .unwrap_or_else(|| state.waiting.len());
+        let waiting = Waiting {
+            id: id.to_string(),
+            rank,
+            tx,
+        };
+        state.waiting.insert(index, waiting);
+        self.schedule(&mut state);
+        Some(rx)
+    }
+
+    /// Remove the waiting task, returning whether it is found.
+    pub fn remove(&self, id: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.waiting.iter().position(|waiting| waiting.id == id) {
+            Some(index) => {
+                state.waiting.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of the tasks before the waiting task.
+    pub fn position(&self, id: &str) -> Option<usize> {
+        let state = self.state.lock().unwrap();
+        state.waiting.iter().position(|waiting| waiting.id == id)
+    }
+
+    fn release(self: &Arc<Self>) {
+        let mut state = self.state.lock().unwrap();
+        state.running -= 1;
+        self.schedule(&mut state);
+    }
+
+    /// Send the permits to the waiting tasks while there are free slots.
+    fn schedule(self: &Arc<Self>, state: &mut State) {
+        while state.running < self.max_running && !state.waiting.is_empty() {
+            let waiting = state.waiting.remove(0);
+            let permit = Permit {
+                queue: Some(self.clone()),
+            };
+            match waiting.tx.send(permit) {
+                Ok(()) => state.running += 1,
+                // the receiver is dropped, so the slot is not taken
+                Err(mut permit) => permit.queue = None,
+            }
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(queue) = self.queue.take() {
+            queue.release();
+        }
+    }
+}
+
+/// Rank of the priority, which is higher for the task judged earlier.
+fn rank(priority: rpc::Priority) -> u8 {
+    match priority {
+        rpc::Priority::High => 2,
+        rpc::Priority::Normal => 1,
+        rpc::Priority::Low => 0,
+    }
+}
@@ -0,0 +1,160 @@
+//! States and reports of the tasks judged by the server.
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::{Mutex, RwLock};
+use tonic::Status;
+
+use super::ana_rpc as rpc;
+use crate::judge;
+
+use rpc::task_status::State;
+
+/// Tasks of the server, whose finished ones are expired after `ttl`.
+pub struct Register {
+    tasks: RwLock<HashMap<String, Entry>>,
+    ttl: Duration,
+}
+
+struct Entry {
+    state: State,
+    created_at: SystemTime,
+    started_at: Option<SystemTime>,
+    finished_at: Option<SystemTime>,
+    cancellation: judge::Cancellation,
+    /// Reports polled by `GetReport`, which are missing if they are streamed.
+    reports: Option<Arc<Mutex<UnboundedReceiver<judge::Report>>>>,
+}
+
+impl Register {
+    pub fn new(ttl: Duration) -> Register {
+        Register {
+            tasks: RwLock::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    /// Add a queued task, returning its cancellation.
+    ///
+    /// A finished task with the same id is replaced. Return `None` if the task
+    /// with the same id is not finished.
+    pub async fn start(
+        &self,
+        id: &str,
+        reports: Option<UnboundedReceiver<judge::Report>>,
+    ) -> Option<judge::Cancellation> {
+        let mut tasks = self.tasks.write().await;
+        self.expire(&mut tasks);
+        if matches!(tasks.get(id), Some(entry) if entry.state != State::Finished) {
+            return None;
+        }
+        let cancellation = judge::Cancellation::new();
+        let entry = Entry {
+            state: State::Queued,
+            created_at: SystemTime::now(),
+            started_at: None,
+            finished_at: None,
+            cancellation: cancellation.clone(),
+            reports: reports.map(|reports| Arc::new(Mutex::new(reports))),
+        };
+        tasks.insert(id.to_string(), entry);
+        Some(cancellation)
+    }
+
+    pub async fn run(&self, id: &str) {
+        if let Some(entry) = self.tasks.write().await.get_mut(id) {
+            entry.state = State::Running;
+            entry.started_at = Some(SystemTime::now());
+        }
+    }
+
+    pub async fn finish(&self, id: &str) {
+        if let Some(entry) = self.tasks.write().await.get_mut(id) {
+            entry.state = State::Finished;
+            entry.finished_at = Some(SystemTime::now());
+        }
+    }
+
+    /// Remove the task which is not judged.
+    pub async fn remove(&self, id: &str) {
+        self.tasks.write().await.remove(id);
+    }
+
+    /// Cancel the task which is not finished, returning whether it is found.
+    pub async fn cancel(&self, id: &str) -> bool {
+        match self.tasks.read().await.get(id) {
+            Some(entry) if entry.state != State::Finished => {
+                entry.cancellation.cancel();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub async fn status(&self, id: &str) -> Option<rpc::TaskStatus> {
+        let mut tasks = self.tasks.write().await;
+        self.expire(&mut tasks);
+        tasks.get(id).map(|entry| entry.status(id))
+    }
+
+    /// Status of all the tasks in the order of creation.
+    pub async fn list(&self) -> Vec<rpc::TaskStatus> {
+        let mut tasks = self.tasks.write().await;
+        self.expire(&mut tasks);
+        let mut entries: Vec<_> = tasks.iter().collect();
+        entries.sort_by_key(|(_, entry)| entry.created_at);
+        entries
+            .into_iter()
+            .map(|(id, entry)| entry.status(id))
+            .collect()
+    }
+
+    /// Get the next report of the task, waiting for it if the task is not finished.
+    pub async fn get_report(&self, id: &str) -> Result<rpc::Report, Status> {
+        let reports = {
+            let mut tasks = self.tasks.write().await;
+            self.expire(&mut tasks);
+            match tasks.get(id) {
+                Some(entry) => entry.reports.clone(),
+                None => return Err(Status::not_found("the task does not exist")),
+            }
+        };
+        let reports = match reports {
+            Some(reports) => reports,
+            None => {
+                return Err(Status::failed_precondition(
+                    "the reports of the task are streamed",
+                ))
+            }
+        };
+        let report = reports.lock().await.recv().await;
+        match report {
+            Some(report) => Ok(rpc::Report::from(report)),
+            None => Err(Status::out_of_range("the task is finished")),
+        }
+    }
+
+    /// Remove the tasks finished for more than `ttl`.
+    fn expire(&self, tasks: &mut HashMap<String, Entry>) {
+        let ttl = self.ttl;
+        tasks.retain(|_, entry| match entry.finished_at {
+            Some(finished_at) => finished_at.elapsed().map_or(true, |elapsed| elapsed < ttl),
+            None => true,
+        });
+    }
+}
+
+impl Entry {
+    fn status(&self, id: &str) -> rpc::TaskStatus {
+        rpc::TaskStatus {
+            id: Some(id.to_string()),
+            state: self.state as i32,
+            created_at: Some(self.created_at.into()),
+            started_at: self.started_at.map(Into::into),
+            finished_at: self.finished_at.map(Into::into),
+            queue_position: None,
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Temporary workspace created from the files uploaded with a task, and the
+//! problems cached with their files.
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use tempfile::TempDir;
+use tokio::fs;
+
+use super::ana_rpc as rpc;
+use crate::workspace::{build, ProblemStore, Workspace};
+
+/// Workspace config used when the task does not have one.
+const DEFAULT_CONFIG: &str = "[runner]\n";
+
+/// Fields of the runner config accepted from untrusted clients.
+///
+/// The other fields change the command, the limits or the isolation of the program.
+const UNTRUSTED_RUNNER_FIELDS: &[&str] = &["envs"];
+
+/// Create a workspace in a temporary directory from the task.
+///
+/// The problem without a config is loaded from `store`.
+/// The workspace is removed when the returned directory is dropped.
+pub async fn create_workspace(
+    task: rpc::Task,
+    store: Option<&ProblemStore>,
+) -> io::Result<(TempDir, Workspace)> {
+    let dir = tempfile::Builder::new().prefix("ana-").tempdir()?;
+    write_workspace(dir.path(), task, store).await?;
+    let workspace = Workspace::from_path(dir.path())?;
+    Ok((dir, workspace))
+}
+
+/// Check that the task of an untrusted client only runs its program in the sandbox.
+///
+/// The build scripts run on the host as the user of Ana, and the runner config except `envs`
+/// weakens the sandbox, so both of them are rejected. The extern program of
+/// an inline problem is rejected as well, which can only be used by a cached problem.
+pub fn check_untrusted(task: &rpc::Task) -> io::Result<()> {
+    if task.build_script.is_some() {
+        return Err(not_allowed("build script"));
+    }
+    if let Some(problem) = task.problem.as_ref() {
+        check_untrusted_problem(problem)?;
+    }
+    let config = match task.config.as_ref() {
+        Some(config) => parse_config(config)?,
+        None => return Ok(()),
+    };
+    if let Some(runner) = config.get("runner").and_then(|runner| runner.as_table()) {
+        if let Some(field) = runner
+            .keys()
+            .find(|field| !UNTRUSTED_RUNNER_FIELDS.contains(&field.as_str()))
+        {
+            return Err(not_allowed(&format!("`{}` of runner config", field)));
+        }
+    }
+    Ok(())
+}
+
+fn check_untrusted_problem(problem: &rpc::Problem) -> io::Result<()> {
+    for file in problem.files.iter() {
+        let path = Path::new(&file.filename);
+        if path.starts_with("extern_program") {
+            return Err(not_allowed("extern program of problem"));
+        }
+        if path.extension() == Some("sh".as_ref()) {
+            return Err(not_allowed("build script of problem"));
+        }
+    }
+    Ok(())
+}
+
+/// Cache the problem in `store` with its id and version.
+pub async fn cache_problem(problem: rpc::Problem, store: &ProblemStore) -> io::Result<()> {
+    let id = problem.id.clone().ok_or_else(|| missing("id of problem"))?;
+    let version = problem.version.clone();
+    let staging_dir = store.staging_dir()?;
+    write_problem(&staging_dir.path().join("problem"), problem).await?;
+    store.insert(&id, version.as_deref(), staging_dir)?;
+    Ok(())
+}
+
+async fn write_workspace(
+    dir: &Path,
+    task: rpc::Task,
+    store: Option<&ProblemStore>,
+) -> io::Result<()> {
+    let config = task.config.unwrap_or_else(|| String::from(DEFAULT_CONFIG));
+    fs::write(dir.join("config.toml"), config).await?;
+
+    let build_dir = dir.join("build");
+    fs::create_dir(&build_dir).await?;
+    let source = task.source.ok_or_else(|| missing("source"))?;
+    let build_script = match task.build_script {
+        Some(build_script) => Some(write_file(&build_dir, build_script).await?),
+        None => None,
+    };
+    let build_config = build::Config {
+        source: write_file(&build_dir, source).await?,
+        language: task.language,
+        build_script,
+        timeout: task
+            .build_timeout
+            .map(|timeout| Duration::new(timeout.seconds as u64, timeout.nanos as u32)),
+        cgroups: None,
+    };
+    let build_config = toml::to_string(&build_config)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fs::write(build_dir.join("config.toml"), build_config).await?;
+
+    let problem = task.problem.ok_or_else(|| missing("problem"))?;
+    if problem.config.is_some() {
+        return write_problem(&dir.join("problem"), problem).await;
+    }
+    let store = store.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The config of problem is missing and the problem store is disabled",
+        )
+    })?;
+    let id = problem.id.ok_or_else(|| missing("id of problem"))?;
+    let cached_problem = store.get(&id, problem.version.as_deref())?;
+    let (store, problem_dir) = (store.clone(), dir.join("problem"));
+    tokio::task::spawn_blocking(move || store.link(&cached_problem, &problem_dir)).await?
+}
+
+async fn write_problem(problem_dir: &Path, problem: rpc::Problem) -> io::Result<()> {
+    fs::create_dir(problem_dir).await?;
+    let config = problem.config.ok_or_else(|| missing("config of problem"))?;
+    fs::write(problem_dir.join("config.toml"), config).await?;
+    for (index, case) in problem.cases.into_iter().enumerate() {
+        let case_dir = problem_dir.join(index.to_string());
+        fs::create_dir(&case_dir).await?;
+        fs::write(case_dir.join("input"), case.input).await?;
+        fs::write(case_dir.join("answer"), case.answer).await?;
+    }
+    for file in problem.files {
+        write_file(problem_dir, file).await?;
+    }
+    Ok(())
+}
+
+/// Write the file into `dir`, returning its relative path.
+///
+/// The file name must be a relative path inside `dir`.
+async fn write_file(dir: &Path, file: rpc::File) -> io::Result<PathBuf> {
+    let filename = PathBuf::from(&file.filename);
+    let is_inside = filename
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if filename.as_os_str().is_empty() || !is_inside {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("The file name {:?} is not a relative path", file.filename),
+        ));
+    }
+    let path = dir.join(&filename);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(&path, file.content).await?;
+    Ok(filename)
+}
+
+fn parse_config(config: &str) -> io::Result<toml::Value> {
+    toml::from_str(config).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn not_allowed(field: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "The {} of task is not allowed from untrusted clients",
+            field
+        ),
+    )
+}
+
+fn missing(field: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("The {} of task is missing", field),
+    )
+}
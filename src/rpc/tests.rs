@@ -0,0 +1,323 @@
+use super::*;
+
+use std::fs;
+use std::path::Path;
+
+use crate::judge::{judge, ResultType};
+
+fn file<P: AsRef<Path>>(path: P, filename: &str) -> io::Result<rpc::File> {
+    Ok(rpc::File {
+        filename: String::from(filename),
+        content: fs::read(path.as_ref().join(filename))?,
+    })
+}
+
+/// Create a task with the files of an example workspace.
+fn example_task(example: &str, problem_files: &[&str]) -> io::Result<rpc::Task> {
+    let example = Path::new("examples/workspace").join(example);
+    let problem_dir = example.join("problem");
+    let mut cases = Vec::new();
+    while problem_dir.join(cases.len().to_string()).exists() {
+        let case_dir = problem_dir.join(cases.len().to_string());
+        cases.push(rpc::problem::Case {
+            input: fs::read(case_dir.join("input"))?,
+            answer: fs::read(case_dir.join("answer"))?,
+        });
+    }
+    Ok(rpc::Task {
+        id: Some(String::from(example.to_string_lossy())),
+        source: Some(file(example.join("build"), "source.c")?),
+        language: Some(String::from("c.gcc")),
+        build_script: None,
+        build_timeout: Some(prost_types::Duration {
+            seconds: 5,
+            nanos: 0,
+        }),
+        problem: Some(rpc::Problem {
+            id: None,
+            version: None,
+            config: Some(fs::read_to_string(problem_dir.join("config.toml"))?),
+            cases,
+            files: problem_files
+                .iter()
+                .map(|filename| file(&problem_dir, filename))
+                .collect::<io::Result<_>>()?,
+        }),
+        config: None,
+        priority: rpc::Priority::Normal as i32,
+    })
+}
+
+async fn judge_task(
+    task: rpc::Task,
+    store: Option<&ProblemStore>,
+) -> io::Result<Vec<judge::Report>> {
+    let (_dir, workspace) = task::create_workspace(task, store).await?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    judge(workspace, tx).await?;
+    Ok(UnboundedReceiverStream::new(rx).collect().await)
+}
+
+#[tokio::test]
+async fn test_normal_task() -> io::Result<()> {
+    let reports = judge_task(example_task("normal_c", &[])?, None).await?;
+    assert_eq!(reports.len(), 3);
+    assert!(reports
+        .iter()
+        .all(|report| report.result == ResultType::Accepted));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_spj_task() -> io::Result<()> {
+    let task = example_task(
+        "spj_c",
+        &["extern_program/config.toml", "extern_program/spj.c"],
+    )?;
+    let reports = judge_task(task, None).await?;
+    assert!(reports
+        .iter()
+        .all(|report| report.result == ResultType::Accepted));
+    Ok(())
+}
+
+#[test]
+fn test_untrusted_task() -> io::Result<()> {
+    let task = example_task("normal_c", &[])?;
+    task::check_untrusted(&task)?;
+
+    let mut script_task = task.clone();
+    script_task.build_script = Some(rpc::File {
+        filename: String::from("build.sh"),
+        content: b"id > /tmp/ana\n".to_vec(),
+    });
+    let err = task::check_untrusted(&script_task).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let mut config_task = task;
+    config_task.config = Some(String::from("[runner]\nenvs = { LANG = \"C\" }\n"));
+    task::check_untrusted(&config_task)?;
+    for config in [
+        "[runner]\nuid = 0\n",
+        "[runner]\ncapabilities = [\"sys_admin\"]\n",
+        "[runner]\nnamespaces = []\n",
+        "[runner]\nno_new_privs = false\n",
+        "[runner]\nmounts = [\"/\"]\n",
+        "[runner]\ncommand = \"/bin/sh\"\n",
+        "[runner]\nargs = [\"-c\", \"id\"]\n",
+        "[runner]\nseccomp = { profile = \"Default\" }\n",
+        "[runner]\ntime_limit_ratio = 100.0\n",
+        "[runner]\nmem_limit_ratio = 100.0\n",
+    ]
+    .iter()
+    {
+        config_task.config = Some(String::from(*config));
+        let err = task::check_untrusted(&config_task).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_untrusted_problem() -> io::Result<()> {
+    use rpc::ana_server::Ana;
+
+    let server = RpcServer::new(1);
+    let spj_task = example_task(
+        "spj_c",
+        &["extern_program/config.toml", "extern_program/spj.c"],
+    )?;
+    let mut script_task = example_task("normal_c", &[])?;
+    script_task.problem.as_mut().unwrap().files.push(rpc::File {
+        filename: String::from("extern_program/build.sh"),
+        content: b"id > /tmp/ana\n".to_vec(),
+    });
+    for task in [spj_task, script_task] {
+        // the rejection happens before the task is spawned in the runtime
+        let status = executor::block_on(server.judge(Request::new(task)))
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    let mut script_task = example_task("normal_c", &[])?;
+    script_task.problem.as_mut().unwrap().files.push(rpc::File {
+        filename: String::from("check.sh"),
+        content: b"id > /tmp/ana\n".to_vec(),
+    });
+    let err = task::check_untrusted(&script_task).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_invalid_filename() -> io::Result<()> {
+    for filename in ["../source.c", "/tmp/source.c", ""].iter() {
+        let mut task = example_task("normal_c", &[])?;
+        task.source.as_mut().unwrap().filename = String::from(*filename);
+        let err = task::create_workspace(task, None).await.err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cached_problem() -> io::Result<()> {
+    let root = tempfile::tempdir()?;
+    let store = ProblemStore::new(root.path())?;
+    let mut task = example_task(
+        "spj_c",
+        &["extern_program/config.toml", "extern_program/spj.c"],
+    )?;
+    let mut problem = task.problem.take().unwrap();
+    problem.id = Some(String::from("spj"));
+    problem.version = Some(String::from("1"));
+    task::cache_problem(problem, &store).await?;
+
+    task.problem = Some(rpc::Problem {
+        id: Some(String::from("spj")),
+        version: Some(String::from("1")),
+        ..Default::default()
+    });
+    let reports = judge_task(task.clone(), Some(&store)).await?;
+    assert_eq!(reports.len(), 3);
+    assert!(reports
+        .iter()
+        .all(|report| report.result == ResultType::Accepted));
+
+    task.problem.as_mut().unwrap().version = Some(String::from("2"));
+    let err = task::create_workspace(task.clone(), Some(&store))
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    let err = task::create_workspace(task, None).await.err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    Ok(())
+}
+
+#[test]
+fn test_untrusted_cache() -> io::Result<()> {
+    use rpc::ana_server::Ana;
+
+    let root = tempfile::tempdir()?;
+    let mut problem = example_task(
+        "spj_c",
+        &["extern_program/config.toml", "extern_program/spj.c"],
+    )?
+    .problem
+    .unwrap();
+    problem.id = Some(String::from("spj"));
+
+    let store = ProblemStore::new(root.path())?;
+    let server = RpcServer::new(1).problem_store(store.clone());
+    let status = server
+        .runtime
+        .block_on(server.cache(Request::new(problem.clone())))
+        .err()
+        .unwrap();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    assert!(store.get("spj", None).is_err());
+
+    let server = RpcServer::new(1)
+        .problem_store(store.clone())
+        .trust_clients();
+    server
+        .runtime
+        .block_on(server.cache(Request::new(problem)))
+        .map_err(|status| io::Error::other(status.to_string()))?;
+    assert!(store.get("spj", None).is_ok());
+    Ok(())
+}
+
+// the judging blocks its thread while waiting for the program
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_cancel_task() -> io::Result<()> {
+    let mut task = example_task("normal_c", &[])?;
+    task.source.as_mut().unwrap().content =
+        b"#include <unistd.h>\nint main() { sleep(10); return 0; }\n".to_vec();
+    let (_dir, workspace) = task::create_workspace(task, None).await?;
+    let cancellation = judge::Cancellation::new();
+    let (tx, rx) = mpsc::unbounded_channel();
+    let cancel = {
+        let cancellation = cancellation.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            cancellation.cancel();
+        })
+    };
+    let start = std::time::Instant::now();
+    let err = judge::judge_cancellable(workspace, tx, &cancellation)
+        .await
+        .err()
+        .unwrap();
+    cancel.await.unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    let reports: Vec<judge::Report> = UnboundedReceiverStream::new(rx).collect().await;
+    assert!(reports.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_register() -> Result<(), Status> {
+    use rpc::task_status::State;
+
+    let register = Register::new(std::time::Duration::from_millis(200));
+    let (tx, rx) = mpsc::unbounded_channel();
+    assert!(register.start("task", Some(rx)).await.is_some());
+    assert!(register.start("task", None).await.is_none());
+    let status = register.status("task").await.unwrap();
+    assert_eq!(status.state, State::Queued as i32);
+    assert!(status.started_at.is_none());
+
+    register.run("task").await;
+    let report = judge::Report::new(ResultType::Accepted, None, String::new());
+    tx.send(report).unwrap();
+    drop(tx);
+    assert_eq!(register.get_report("task").await?.result, 0);
+    assert_eq!(
+        register.status("task").await.unwrap().state,
+        State::Running as i32
+    );
+    register.finish("task").await;
+    let status = register.status("task").await.unwrap();
+    assert_eq!(status.state, State::Finished as i32);
+    assert!(status.finished_at.is_some());
+    assert!(!register.cancel("task").await);
+    let err = register.get_report("task").await.err().unwrap();
+    assert_eq!(err.code(), tonic::Code::OutOfRange);
+    assert_eq!(register.list().await.len(), 1);
+
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    assert!(register.status("task").await.is_none());
+    assert!(register.list().await.is_empty());
+    let err = register.get_report("task").await.err().unwrap();
+    assert_eq!(err.code(), tonic::Code::NotFound);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_queue() {
+    use rpc::Priority;
+
+    let queue = Arc::new(Queue::new(1, 2));
+    let first = queue.push("first", Priority::Normal).unwrap();
+    let first = first.await.unwrap();
+    let low = queue.push("low", Priority::Low).unwrap();
+    let normal = queue.push("normal", Priority::Normal).unwrap();
+    assert!(queue.push("full", Priority::High).is_none());
+    assert_eq!(queue.position("normal"), Some(0));
+    assert_eq!(queue.position("low"), Some(1));
+    assert_eq!(queue.position("first"), None);
+
+    assert!(queue.remove("normal"));
+    assert!(normal.await.is_err());
+    let high = queue.push("high", Priority::High).unwrap();
+    assert_eq!(queue.position("high"), Some(0));
+    drop(first);
+    let second = high.await.unwrap();
+    assert_eq!(queue.position("low"), Some(0));
+    drop(second);
+    low.await.unwrap();
+}
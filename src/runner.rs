@@ -6,25 +6,55 @@ use std::process::{Child, Command, Stdio, ChildStderr};
 use std::time::Duration;
 use std::collections::BTreeMap;
 
-use crate::process::{cgroup, cgroup::CommandExt as _, CommandExt as _};
+use nix::sched::CloneFlags;
+use nix::unistd::{getegid, geteuid};
+
+#[cfg(feature = "cap-ng")]
+use crate::process::capng::{self, CommandExt as _};
+#[cfg(feature = "seccomp")]
+use crate::process::seccomp::{self, CommandExt as _};
+use crate::process::{self, cgroup, cgroup::CommandExt as _, CommandExt as _};
+use crate::workspace::runtime::{Namespace, SeccompConfig};
+#[cfg(feature = "seccomp")]
+use crate::workspace::runtime::{SeccompAction, SeccompCmpOp, SeccompProfile};
 use crate::workspace::{RunnerConfig, RuntimeDir};
 
+/// The overflow uid and gid used as the owner of the user namespace
+/// or the user of the program without it.
+const NOBODY: u32 = 65534;
+
 pub struct Runner {
     inner: Command,
     cg: cgroup::Context,
     proc_path: Option<PathBuf>,
+    #[cfg_attr(not(feature = "cap-ng"), allow(dead_code))]
+    capabilities: Option<Vec<String>>,
+    no_new_privs: bool,
+    /// The uid and gid of the program if it is run by root without a user namespace.
+    user: Option<(u32, u32)>,
+    #[cfg_attr(not(feature = "seccomp"), allow(dead_code))]
+    seccomp: Option<SeccompConfig>,
 }
 
 impl Runner {
     pub async fn new(runtime_dir: &RuntimeDir, config: &RunnerConfig) -> io::Result<Runner> {
+        let cgroups_builder = config.cgroups.clone().unwrap_or_default().builder();
+        Runner::with_cgroup(runtime_dir, config, cgroups_builder).await
+    }
+
+    /// Create a runner whose program is attached to the cgroup built by `cgroups_builder`.
+    pub async fn with_cgroup(
+        runtime_dir: &RuntimeDir,
+        config: &RunnerConfig,
+        cgroups_builder: cgroup::Builder,
+    ) -> io::Result<Runner> {
         let mut with_proc = false;
         let mut proc_path = None;
+        let mut user = None;
 
-        let executable_file = match &config.command {
-            Some(executable) => executable.clone(),
-            None => PathBuf::from("/main"),
-        };
-        let mut command = Command::new(&executable_file);
+        // the built program is passed to the command if it is not executable itself
+        let executable_file = PathBuf::from("/main");
+        let mut command = Command::new(config.command.as_ref().unwrap_or(&executable_file));
         let args: Vec<_> = config
             .args
             .clone()
@@ -42,23 +72,73 @@ impl Runner {
             .args(args)
             .env_clear()
             .envs(config.envs.as_ref().unwrap_or(&empty_envs))
-            .current_dir(&runtime_dir);
+            .current_dir(runtime_dir);
 
         if let Some(rootfs_config) = config.rootfs.as_ref() {
             with_proc = rootfs_config.with_proc;
         }
 
-        // TODO: handle cgroup configurations
-        // let cgroups_config = config.cgroups.unwrap_or_default();
-        let cgroups_context = cgroup::Builder::new()
-            .cpu_controller(true)
-            .cpuacct_controller(true)
-            .memory_controller(true)
-            .cpuset_controller(true, 1)
-            .build()
-            .await?;
+        let cgroups_context = cgroups_builder.build().await?;
         command.cgroup(cgroups_context.clone());
-        command.unshare_all_ns();
+        let namespaces = config
+            .namespaces
+            .clone()
+            .unwrap_or_else(Namespace::defaults);
+        let flags = namespaces
+            .iter()
+            .fold(CloneFlags::CLONE_FILES, |flags, ns| flags | ns.clone_flag());
+        if process::rootless() {
+            // root in the user namespace can only be mapped to the current user without root
+            if config.uid.is_some() || config.gid.is_some() {
+                log::warn!("The uid and gid config are ignored in rootless mode");
+            }
+            command.unshare_ns(
+                flags | CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWNS,
+                geteuid().as_raw(),
+                getegid().as_raw(),
+            );
+            if let Some(rootfs_config) = config.rootfs.as_ref() {
+                command.overlay(
+                    runtime_dir.to_path_buf(),
+                    rootfs_config.base_path.clone(),
+                    runtime_dir.work_dir(),
+                );
+            }
+        } else {
+            let uid = config.uid.unwrap_or(NOBODY);
+            let gid = config.gid.unwrap_or(NOBODY);
+            command.unshare_ns(flags, uid, gid);
+            // the program must not run as root outside a user namespace
+            if !flags.contains(CloneFlags::CLONE_NEWUSER) {
+                user = Some((uid, gid));
+            }
+        }
+        if let Some(mounts) = config.mounts.as_ref() {
+            // the mounts of the host would be changed without a mount namespace
+            if !flags.contains(CloneFlags::CLONE_NEWNS) && !process::rootless() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "The mounts config requires the MOUNT namespace",
+                ));
+            }
+            for source in mounts {
+                // the processes of the host are not exposed to the program
+                if source.starts_with("/proc") {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "The proc filesystem of the host can not be mounted",
+                    ));
+                }
+                let target = runtime_dir.join(source.strip_prefix("/").unwrap_or(source));
+                std::fs::create_dir_all(&target)?;
+                command.bind_read_only(source, &target)?;
+            }
+            // the toolchain gets a new proc filesystem showing only the processes of the program
+            if flags.contains(CloneFlags::CLONE_NEWPID) {
+                std::fs::create_dir_all(runtime_dir.join("proc"))?;
+                with_proc = true;
+            }
+        }
         command.chroot(runtime_dir);
 
         if with_proc {
@@ -66,11 +146,22 @@ impl Runner {
             proc_path = Some(runtime_dir.join("proc"));
         }
 
-        dbg!(&cgroups_context);
+        if config.capabilities.is_some() && cfg!(not(feature = "cap-ng")) {
+            log::warn!("The capabilities config is ignored without the cap-ng feature");
+        }
+        if config.seccomp.is_some() && cfg!(not(feature = "seccomp")) {
+            log::warn!("The seccomp config is ignored without the seccomp feature");
+        }
+
+        log::debug!("Run the program in cgroup {:?}", &cgroups_context);
         let res = Runner {
             inner: command,
             cg: cgroups_context,
-            proc_path: proc_path,
+            proc_path,
+            capabilities: Some(config.capabilities.clone().unwrap_or_default()),
+            no_new_privs: config.no_new_privs.unwrap_or(true),
+            user,
+            seccomp: config.seccomp.clone(),
         };
 
         Ok(res)
@@ -91,12 +182,105 @@ impl Runner {
         self
     }
 
+    /// Limit the size of files written by the program.
+    ///
+    /// The program is killed by `SIGXFSZ` when it exceeds the limit.
+    pub fn output_limit(&mut self, size: usize) -> &mut Runner {
+        self.inner.file_size_limit(size as u64);
+        self
+    }
+
     pub fn spawn(&mut self) -> io::Result<Program> {
+        // the capabilities are dropped after the other settings which need them
+        #[cfg(feature = "cap-ng")]
+        if let Some(names) = self.capabilities.take() {
+            let keep = names
+                .iter()
+                .map(|name| {
+                    capng::Capability::from_name(name).ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("No such capability {}", name),
+                        )
+                    })
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            self.inner.capabilities(keep);
+        }
+        // the user is switched after the capabilities are set, which requires root
+        if let Some((uid, gid)) = self.user.take() {
+            self.inner.switch_user(uid, gid);
+        }
+        if self.no_new_privs {
+            self.no_new_privs = false;
+            self.inner.no_new_privs();
+        }
+        // the filter must be the last one loaded before exec
+        // so that the other settings are not restricted by it
+        #[cfg(feature = "seccomp")]
+        if let Some(config) = self.seccomp.take() {
+            self.inner.seccomp(seccomp_context(&config)?);
+        }
         let child = self.inner.spawn()?;
         Ok(Program::new(child, self.cg.clone(), self.proc_path.clone()))
     }
 }
 
+/// Build the seccomp context from the profile and the rules.
+#[cfg(feature = "seccomp")]
+fn seccomp_context(config: &SeccompConfig) -> io::Result<seccomp::Context> {
+    fn act(action: SeccompAction) -> seccomp::Act {
+        match action {
+            SeccompAction::Allow => seccomp::Act::Allow,
+            SeccompAction::Kill => seccomp::Act::Kill,
+        }
+    }
+
+    fn add_rule(ctx: &mut seccomp::Context, rule: seccomp::Rule) -> io::Result<()> {
+        ctx.add_rule(rule)
+            .map_err(|e| io::Error::other(format!("Failed to add seccomp rule: {}", e)))
+    }
+
+    let default_action = config.default_action.unwrap_or(SeccompAction::Kill);
+    let mut ctx = seccomp::Context::new(act(default_action));
+
+    let whitelists: &[&[&str]] = match config.profile {
+        Some(SeccompProfile::Default) => &[seccomp::DEFAULT_WHITELIST],
+        Some(SeccompProfile::Jvm) => &[seccomp::DEFAULT_WHITELIST, seccomp::JVM_WHITELIST],
+        None => &[],
+    };
+    for name in whitelists.iter().flat_map(|whitelist| whitelist.iter()) {
+        match seccomp::Syscall::resolve(name) {
+            Some(syscall) => add_rule(&mut ctx, seccomp::Rule::whitelist(syscall))?,
+            None => log::debug!("Syscall {} is not available", name),
+        }
+    }
+
+    for rule in config.rules.iter().flatten() {
+        let syscall = seccomp::Syscall::resolve(&rule.syscall).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("No such syscall {}", rule.syscall),
+            )
+        })?;
+        let mut seccomp_rule = seccomp::Rule::new(act(rule.action), syscall);
+        for arg in rule.args.iter().flatten() {
+            let op = match arg.op {
+                SeccompCmpOp::NE => seccomp::CmpOp::NE,
+                SeccompCmpOp::LT => seccomp::CmpOp::LT,
+                SeccompCmpOp::LE => seccomp::CmpOp::LE,
+                SeccompCmpOp::EQ => seccomp::CmpOp::EQ,
+                SeccompCmpOp::GE => seccomp::CmpOp::GE,
+                SeccompCmpOp::GT => seccomp::CmpOp::GT,
+            };
+            seccomp_rule.match_arg(op, arg.value);
+        }
+        add_rule(&mut ctx, seccomp_rule)?;
+    }
+
+    Ok(ctx)
+}
+
 pub struct Program {
     inner: Child,
     cg: cgroup::Context,
@@ -126,6 +310,34 @@ impl Program {
         Ok(res)
     }
 
+    /// Check whether the program is killed by the OOM killer
+    /// because the memory usage reaches the limit of the cgroup.
+    pub fn oom_killed(&self) -> io::Result<bool> {
+        let controller = match self.cg.memory_controller() {
+            Some(controller) => controller,
+            None => return Ok(false),
+        };
+        match controller.oom_kill()? {
+            Some(count) => Ok(count > 0),
+            // old kernels only count the times the limit is hit
+            None => Ok(controller.failcnt()? > 0),
+        }
+    }
+
+    /// Check whether the program failed to fork
+    /// because the number of processes reaches the limit of the cgroup.
+    pub fn pids_limit_hit(&self) -> io::Result<bool> {
+        match self.cg.pids_controller() {
+            Some(controller) => Ok(controller.failcnt()? > 0),
+            None => Ok(false),
+        }
+    }
+
+    /// The cgroup which the program runs in.
+    pub fn cgroup(&self) -> &cgroup::Context {
+        &self.cg
+    }
+
     pub fn stderr(&mut self) -> Option<&mut ChildStderr> {
         self.inner.stderr.as_mut()
     }
@@ -133,6 +345,15 @@ impl Program {
 
 impl Drop for Program {
     fn drop(&mut self) {
+        // the program is not waited if the judge fails, so it is killed and reaped here
+        if let Ok(None) = self.inner.try_wait() {
+            if let Err(e) = self.cg.kill() {
+                log::warn!("Failed to kill the programs in the cgroup {}", e);
+            }
+            let _ = self.inner.kill();
+            let _ = self.inner.wait();
+        }
+
         unsafe {
             if let Err(e) = self.cg.remove() {
                 log::debug!("Error when dropping cgroup {}", e);
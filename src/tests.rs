@@ -1,19 +1,21 @@
 use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
 
-use tempfile;
-use tokio::sync::mpsc;
+use lazy_static::lazy_static;
+use tokio::sync::{mpsc, Mutex, MutexGuard};
 use tokio_stream::{wrappers::UnboundedReceiverStream, StreamExt};
 
-use crate::judge::{judge, ResultType};
-use crate::workspace::Workspace;
-
-use env_logger;
+use crate::judge::{judge, run_program, Execution, ResultType, Score};
+use crate::language;
+use crate::workspace::problem::ResourceLimit;
+use crate::workspace::{RunnerConfig, RuntimeDir, RuntimeHolder, Workspace};
 
 #[tokio::test]
 async fn test_normal_c() -> io::Result<()> {
-    env_logger::init();
+    let _ = env_logger::try_init();
     const EXAMPLE_WORKSPACE: &str = "examples/workspace/normal_c";
     let workspace = tempfile::tempdir()?;
     copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
@@ -22,9 +24,70 @@ async fn test_normal_c() -> io::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_languages() -> io::Result<()> {
+    let sources = [
+        (
+            "c.gcc",
+            "source.c",
+            include_str!("../examples/code/source.c"),
+        ),
+        (
+            "cpp.g++",
+            "source.cpp",
+            include_str!("../examples/code/source.cpp"),
+        ),
+        (
+            "java.openjdk",
+            "Main.java",
+            "import java.util.Scanner;\n\
+             class Main {\n\
+               public static void main(String[] args) {\n\
+                 Scanner in = new Scanner(System.in);\n\
+                 System.out.println(in.nextInt() + in.nextInt());\n\
+               }\n\
+             }\n",
+        ),
+        (
+            "py.cpython",
+            "source.py",
+            "a, b = map(int, input().split())\nprint(a + b)\n",
+        ),
+    ];
+    for (language, source_file, source) in sources.iter() {
+        const EXAMPLE_WORKSPACE: &str = "examples/workspace/normal_c";
+        let workspace = tempfile::tempdir()?;
+        copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+        fs::remove_file(workspace.path().join("build/source.c"))?;
+        fs::write(workspace.path().join("build").join(source_file), source)?;
+        // the runner config is left to the language
+        fs::write(
+            workspace.path().join("build/config.toml"),
+            format!(
+                "source = \"{}\"\nlanguage = \"{}\"\n",
+                source_file, language
+            ),
+        )?;
+        fs::write(workspace.path().join("config.toml"), "")?;
+        let workspace = Workspace::from_path(workspace.path())?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        judge(workspace, tx).await?;
+        let reports: Vec<_> = UnboundedReceiverStream::new(rx).collect().await;
+        assert!(
+            reports
+                .iter()
+                .all(|report| report.result == ResultType::Accepted),
+            "{}: {:?}",
+            language,
+            reports
+        );
+    }
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_spj_c() -> io::Result<()> {
-    env_logger::init();
+    let _ = env_logger::try_init();
     const EXAMPLE_WORKSPACE: &str = "examples/workspace/spj_c";
     let workspace = tempfile::tempdir()?;
     copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
@@ -43,10 +106,554 @@ async fn test_custom_script() -> io::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_interactive_c() -> io::Result<()> {
+    const EXAMPLE_WORKSPACE: &str = "examples/workspace/interactive_c";
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    test_workspace(workspace).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_interactive_py() -> io::Result<()> {
+    const EXAMPLE_WORKSPACE: &str = "examples/workspace/interactive_c";
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    let extern_program = workspace.path().join("problem/extern_program");
+    fs::remove_file(extern_program.join("interactor.c"))?;
+    // the interactor is run by the runner command of its language
+    fs::write(
+        extern_program.join("interactor.py"),
+        "import sys\n\
+         secret = int(open(sys.argv[1]).read())\n\
+         max_guesses = int(open(sys.argv[2]).read())\n\
+         for _ in range(max_guesses):\n\
+         \x20   guess = int(input())\n\
+         \x20   print('<' if guess < secret else '>' if guess > secret else '=', flush=True)\n\
+         \x20   if guess == secret:\n\
+         \x20       sys.exit(0)\n\
+         sys.exit(1)\n",
+    )?;
+    fs::write(
+        extern_program.join("config.toml"),
+        "source = \"interactor.py\"\nlanguage = \"py.cpython\"\n",
+    )?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    test_workspace(workspace).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_subtask_c() -> io::Result<()> {
+    const EXAMPLE_WORKSPACE: &str = "examples/workspace/subtask_c";
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    judge(workspace, tx).await?;
+    let reports: Vec<_> = UnboundedReceiverStream::new(rx).collect().await;
+    assert!(reports
+        .iter()
+        .all(|report| report.result == ResultType::Accepted));
+    // every case is judged only once for the first subtask containing it
+    let cases: Vec<_> = reports
+        .iter()
+        .filter(|report| report.score.is_none())
+        .map(|report| (report.case, report.subtask))
+        .collect();
+    assert_eq!(
+        cases,
+        vec![(Some(0), Some(0)), (Some(1), Some(1)), (Some(2), Some(1))]
+    );
+    let scores: Vec<_> = reports.iter().filter_map(|report| report.score).collect();
+    assert_eq!(
+        scores,
+        vec![
+            Score {
+                subtask: Some(0),
+                score: 30.0,
+                full_score: 30.0
+            },
+            Score {
+                subtask: Some(1),
+                score: 70.0,
+                full_score: 70.0
+            },
+            Score {
+                subtask: None,
+                score: 100.0,
+                full_score: 100.0
+            },
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_float_c() -> io::Result<()> {
+    const EXAMPLE_WORKSPACE: &str = "examples/workspace/float_c";
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    test_workspace(workspace).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_run_all_cases() -> io::Result<()> {
+    const EXAMPLE_WORKSPACE: &str = "examples/workspace/normal_c";
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    fs::write(workspace.path().join("problem/0/answer"), "0")?;
+    let config = fs::read_to_string(workspace.path().join("config.toml"))?;
+    fs::write(
+        workspace.path().join("config.toml"),
+        format!("run_all_cases = true\n{}", config),
+    )?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    judge(workspace, tx).await?;
+    let reports: Vec<_> = UnboundedReceiverStream::new(rx).collect().await;
+    let results: Vec<_> = reports.iter().map(|report| report.result.clone()).collect();
+    assert_eq!(
+        results,
+        vec![
+            ResultType::WrongAnswer,
+            ResultType::Accepted,
+            ResultType::WrongAnswer
+        ]
+    );
+    assert!(reports.last().unwrap().summary);
+    assert!(reports[..2].iter().all(|report| !report.summary));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_output_limit() -> io::Result<()> {
+    const EXAMPLE_WORKSPACE: &str = "examples/workspace/normal_c";
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    fs::write(
+        workspace.path().join("build/source.c"),
+        "#include <stdio.h>\nint main() { for (;;) puts(\"ana\"); }\n",
+    )?;
+    let config = fs::read_to_string(workspace.path().join("problem/config.toml"))?;
+    fs::write(
+        workspace.path().join("problem/config.toml"),
+        format!("{}\noutput = 1024\n", config),
+    )?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    judge(workspace, tx).await?;
+    let reports: Vec<_> = UnboundedReceiverStream::new(rx).collect().await;
+    assert_eq!(reports[0].result, ResultType::OutputLimitExceeded);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_memory_limit() -> io::Result<()> {
+    const EXAMPLE_WORKSPACE: &str = "examples/workspace/normal_c";
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    fs::write(
+        workspace.path().join("build/source.c"),
+        "#include <stdlib.h>\n#include <string.h>\n\
+         int main() { for (;;) memset(malloc(1 << 20), 1, 1 << 20); }\n",
+    )?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    judge(workspace, tx).await?;
+    let reports: Vec<_> = UnboundedReceiverStream::new(rx).collect().await;
+    assert_eq!(reports[0].result, ResultType::MemoryLimitExceeded);
+    assert!(reports[0].usage.unwrap().real_time < Duration::from_secs(1));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cgroups_memory_limit() -> io::Result<()> {
+    const EXAMPLE_WORKSPACE: &str = "examples/workspace/normal_c";
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    fs::write(
+        workspace.path().join("build/source.c"),
+        "#include <stdio.h>\n#include <stdlib.h>\n#include <string.h>\n\
+         int main() {\n\
+           int a, b;\n\
+           char *buffer = malloc(16 << 20);\n\
+           memset(buffer, 1, 16 << 20);\n\
+           while (scanf(\"%d%d\", &a, &b) == 2) printf(\"%d\\n\", a + b);\n\
+           return buffer[12345] - 1;\n\
+         }\n",
+    )?;
+    // the smaller limit of the config is used instead of the one of the problem
+    let config = fs::read_to_string(workspace.path().join("config.toml"))?;
+    fs::write(
+        workspace.path().join("config.toml"),
+        format!("{}\n[runner.cgroups]\nmemory_limit = 8388608\n", config),
+    )?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    judge(workspace, tx).await?;
+    let reports: Vec<_> = UnboundedReceiverStream::new(rx).collect().await;
+    assert_eq!(reports[0].result, ResultType::MemoryLimitExceeded);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_process_limit() -> io::Result<()> {
+    const EXAMPLE_WORKSPACE: &str = "examples/workspace/normal_c";
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    fs::write(
+        workspace.path().join("build/source.c"),
+        "#include <sys/wait.h>\n#include <unistd.h>\n\
+         int main() {\n\
+           for (int i = 0; i < 64; i++) {\n\
+             pid_t pid = fork();\n\
+             if (pid == 0) { usleep(100000); return 0; }\n\
+             if (pid < 0) { while (wait(NULL) > 0); return 1; }\n\
+           }\n\
+           while (wait(NULL) > 0);\n\
+           return 0;\n\
+         }\n",
+    )?;
+    let config = fs::read_to_string(workspace.path().join("problem/config.toml"))?;
+    fs::write(
+        workspace.path().join("problem/config.toml"),
+        format!("{}\nprocesses = 8\n", config),
+    )?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    judge(workspace, tx).await?;
+    let reports: Vec<_> = UnboundedReceiverStream::new(rx).collect().await;
+    assert_eq!(reports[0].result, ResultType::RuntimeError);
+    assert_eq!(
+        reports[0].message,
+        "The number of processes exceeds the limit"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_interactor_killed_on_error() -> io::Result<()> {
+    const EXAMPLE_WORKSPACE: &str = "examples/workspace/interactive_c";
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    fs::write(
+        workspace.path().join("problem/extern_program/interactor.c"),
+        "#include <unistd.h>\nint main() { for (;;) pause(); }\n",
+    )?;
+    // the program fails to run after the interactor is started
+    let config = fs::read_to_string(workspace.path().join("config.toml"))?;
+    fs::write(
+        workspace.path().join("config.toml"),
+        format!("{}\ncommand = \"/missing\"\n", config),
+    )?;
+    let checker_dir = workspace.path().join("checker");
+    let workspace = Workspace::from_path(workspace.path())?;
+    let (tx, _rx) = mpsc::unbounded_channel();
+    assert!(judge(workspace, tx).await.is_err());
+    for entry in fs::read_dir("/proc")? {
+        // the root of the leaked interactor is shown as deleted with the workspace
+        if let Ok(root) = fs::read_link(entry?.path().join("root")) {
+            let root = root.to_string_lossy().into_owned();
+            assert!(!root.starts_with(&*checker_dir.to_string_lossy()));
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_interactor_limits() -> io::Result<()> {
+    use std::time::Instant;
+
+    const EXAMPLE_WORKSPACE: &str = "examples/workspace/interactive_c";
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    fs::write(
+        workspace.path().join("problem/extern_program/interactor.c"),
+        "#include <stdlib.h>\n\
+         int main() {\n\
+           volatile char *buffer = malloc(64 << 20);\n\
+           for (int i = 0; i < 64 << 20; i += 4096) buffer[i] = 1;\n\
+           return 0;\n\
+         }\n",
+    )?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    judge(workspace, tx).await?;
+    let reports: Vec<_> = UnboundedReceiverStream::new(rx).collect().await;
+    // the interactor is killed by the memory limit of the program
+    assert_eq!(reports[0].result, ResultType::SystemError);
+
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    for source in ["build/source.c", "problem/extern_program/interactor.c"].iter() {
+        fs::write(
+            workspace.path().join(source),
+            "#include <unistd.h>\nint main() { for (;;) pause(); }\n",
+        )?;
+    }
+    let config = fs::read_to_string(workspace.path().join("problem/config.toml"))?;
+    fs::write(
+        workspace.path().join("problem/config.toml"),
+        config.replace("real_time = { secs = 1", "real_time = { secs = 3"),
+    )?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    let start_time = Instant::now();
+    judge(workspace, tx).await?;
+    // both of them are killed at the same deadline instead of one after another
+    assert!(start_time.elapsed() < Duration::from_secs(5));
+    let reports: Vec<_> = UnboundedReceiverStream::new(rx).collect().await;
+    assert_eq!(reports[0].result, ResultType::TimeLimitExceeded);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_spj_sandbox() -> io::Result<()> {
+    use std::time::Instant;
+
+    const EXAMPLE_WORKSPACE: &str = "examples/workspace/spj_c";
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    // the files of the host are not visible to the special judge
+    fs::write(
+        workspace.path().join("problem/extern_program/spj.c"),
+        "#include <unistd.h>\nint main() { return access(\"/etc/passwd\", F_OK) == 0; }\n",
+    )?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    judge(workspace, tx).await?;
+    let reports: Vec<_> = UnboundedReceiverStream::new(rx).collect().await;
+    assert!(reports
+        .iter()
+        .all(|report| report.result == ResultType::Accepted));
+
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    fs::write(
+        workspace.path().join("problem/extern_program/spj.c"),
+        "#include <unistd.h>\nint main() { for (;;) pause(); }\n",
+    )?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    let start_time = Instant::now();
+    judge(workspace, tx).await?;
+    // the special judge is killed by the real time limit of the program
+    assert!(start_time.elapsed() < Duration::from_secs(5));
+    let reports: Vec<_> = UnboundedReceiverStream::new(rx).collect().await;
+    assert_eq!(reports[0].result, ResultType::SystemError);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_user_namespace() -> io::Result<()> {
+    const EXAMPLE_WORKSPACE: &str = "examples/workspace/normal_c";
+    let workspace = tempfile::tempdir()?;
+    copy_dir(EXAMPLE_WORKSPACE, workspace.path())?;
+    let config = fs::read_to_string(workspace.path().join("config.toml"))?;
+    fs::write(
+        workspace.path().join("config.toml"),
+        format!(
+            "{}\nnamespaces = [\"IPC\", \"NETWORK\", \"MOUNT\", \"PID\", \"USER\", \"UTS\"]\n",
+            config
+        ),
+    )?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    test_workspace(workspace).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_run_program() -> io::Result<()> {
+    let programs = [
+        ("return 0;", None, Some(0)),
+        ("return 3;", Some(ResultType::RuntimeError), Some(3)),
+        ("for (;;);", Some(ResultType::TimeLimitExceeded), None),
+    ];
+    for (body, result, code) in programs.iter() {
+        let source = format!("int main() {{ {} }}", body);
+        let execution = run_c_program(&source, &RunnerConfig::default()).await?;
+        assert_eq!(&execution.result, result);
+        assert_eq!(&execution.exit_status.code(), code);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_default_user() -> io::Result<()> {
+    // the program exits with 1 if it runs as root
+    let execution = run_c_program(
+        "#include <unistd.h>\nint main() { return getuid() == 0 || getgid() == 0; }\n",
+        &RunnerConfig::default(),
+    )
+    .await?;
+    assert_eq!(execution.exit_status.code(), Some(0));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mounts() -> io::Result<()> {
+    let config: RunnerConfig = toml::from_str("mounts = [ \"/usr\" ]\n")
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    // only the mounted directories and a new proc filesystem are visible to the program
+    let execution = run_c_program(
+        "#include <unistd.h>\nint main() { return access(\"/usr/bin\", F_OK) || access(\"/proc/self\", F_OK) || !access(\"/etc\", F_OK); }\n",
+        &config,
+    )
+    .await?;
+    assert_eq!(execution.exit_status.code(), Some(0));
+
+    let config: RunnerConfig = toml::from_str("mounts = [ \"/proc\" ]\n")
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let error = run_c_program("int main() {}\n", &config).await.unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    Ok(())
+}
+
+#[cfg(feature = "seccomp")]
+#[tokio::test]
+async fn test_seccomp() -> io::Result<()> {
+    let config: RunnerConfig = toml::from_str("[seccomp]\nprofile = \"Default\"\n")
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let programs = [
+        ("#include <stdio.h>\nint main() { puts(\"hello\"); }", None),
+        // abort is not restricted by the default profile
+        (
+            "#include <assert.h>\nint main() { assert(0); }",
+            Some(ResultType::RuntimeError),
+        ),
+        (
+            "#include <sys/socket.h>\nint main() { return socket(AF_INET, SOCK_STREAM, 0); }",
+            Some(ResultType::RestrictedFunction),
+        ),
+    ];
+    for (source, result) in programs.iter() {
+        let execution = run_c_program(source, &config).await?;
+        assert_eq!(&execution.result, result);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_language_tools() -> io::Result<()> {
+    use crate::language::Language;
+
+    let languages = Language::all()?;
+    assert!(languages.iter().any(|language| language.name() == "c.gcc"));
+    assert!(languages
+        .iter()
+        .all(|language| language.name() != "c.default"));
+    let gcc = Language::new("c.gcc").unwrap();
+    assert!(gcc.missing_tools()?.is_empty());
+    assert!(gcc.is_default());
+    assert_eq!(gcc.extension(), "c");
+    assert!(gcc.version().unwrap().contains("gcc"));
+    assert!(Language::new("../lang/c.gcc").is_none());
+
+    let lang_dir = tempfile::tempdir()?;
+    let mut search_paths = vec![lang_dir.path().to_owned()];
+    search_paths.extend(language::search_paths());
+    fs::create_dir(lang_dir.path().join("c.missing"))?;
+    fs::write(
+        lang_dir.path().join("c.missing/build.sh"),
+        "if [ -n \"$SOURCE_FILE\" ]; then\n  CC=/usr/bin/ana-missing-compiler\nfi\n$CC $SOURCE_FILE\n",
+    )?;
+    fs::write(
+        lang_dir.path().join("c.missing/builder.toml"),
+        "tools = [ \"/usr/bin/ana-missing-compiler\" ]\n",
+    )?;
+    let missing = Language::find_in("c.missing", &search_paths).unwrap();
+    assert_eq!(
+        missing.missing_tools()?,
+        vec!["/usr/bin/ana-missing-compiler"]
+    );
+    assert!(!missing.is_default());
+    assert!(Language::all_in(&search_paths)?
+        .iter()
+        .any(|language| language.name() == "c.missing"));
+    assert!(Language::new("c.missing").is_none());
+
+    let workspace = tempfile::tempdir()?;
+    copy_dir("examples/workspace/normal_c", workspace.path())?;
+    fs::write(
+        workspace.path().join("build/config.toml"),
+        "source = \"source.c\"\nlanguage = \"c.missing\"\n",
+    )?;
+    let workspace = Workspace::from_path(workspace.path())?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    {
+        let _search_paths = SearchPathsGuard::new(search_paths).await;
+        judge(workspace, tx).await?;
+    }
+    let reports: Vec<_> = UnboundedReceiverStream::new(rx).collect().await;
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].result, ResultType::SystemError);
+    assert!(reports[0].message.contains("ana-missing-compiler"));
+    Ok(())
+}
+
+lazy_static! {
+    /// Lock held by the tests while they change the search paths of the languages.
+    static ref SEARCH_PATHS_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Search the languages in the given paths until the guard is dropped.
+///
+/// The paths should only add languages to the default ones,
+/// which are still found by the other tests running at the same time.
+struct SearchPathsGuard {
+    default_paths: Vec<PathBuf>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl SearchPathsGuard {
+    async fn new(search_paths: Vec<PathBuf>) -> SearchPathsGuard {
+        let lock = SEARCH_PATHS_LOCK.lock().await;
+        let default_paths = language::search_paths();
+        language::set_search_paths(search_paths);
+        SearchPathsGuard {
+            default_paths,
+            _lock: lock,
+        }
+    }
+}
+
+impl Drop for SearchPathsGuard {
+    fn drop(&mut self) {
+        language::set_search_paths(self.default_paths.clone());
+    }
+}
+
+/// Compile the C source statically and run it by `run_program` with the config.
+async fn run_c_program(source: &str, config: &RunnerConfig) -> io::Result<Execution> {
+    let dir = tempfile::tempdir()?;
+    let runtime_dir = RuntimeDir::from_path(dir.path().join("runtime"));
+    let _runtime_holder = RuntimeHolder::new(&runtime_dir, None)?;
+    let limit = ResourceLimit {
+        cpu_time: Duration::from_millis(500),
+        real_time: Duration::from_millis(500),
+        memory: 64 * 1024 * 1024,
+        output: None,
+        processes: None,
+    };
+    fs::write(dir.path().join("main.c"), source)?;
+    let status = Command::new("gcc")
+        .arg("-static")
+        .arg("-o")
+        .arg(runtime_dir.executable_file())
+        .arg(dir.path().join("main.c"))
+        .status()?;
+    assert!(status.success());
+    run_program(&runtime_dir, config, &limit, Stdio::null(), Stdio::null()).await
+}
+
 fn copy_dir<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> io::Result<()> {
     let (src, dst) = (src.as_ref(), dst.as_ref());
-    let mut rd = fs::read_dir(src)?;
-    while let Some(de) = rd.next() {
+    for de in fs::read_dir(src)? {
         let dir_entry = de?;
         let dst = dst.join(dir_entry.path().strip_prefix(src).unwrap());
         let file_type = dir_entry.file_type()?;
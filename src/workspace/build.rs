@@ -7,6 +7,9 @@ use std::time::Duration;
 use serde::{Deserialize, Serialize};
 use toml;
 
+use super::runtime::CgroupsConfig;
+use crate::language::Language;
+
 #[derive(Clone)]
 pub struct BuildDir {
     inner: PathBuf,
@@ -32,6 +35,19 @@ impl BuildDir {
         }
     }
 
+    /// Language of the source file, which is `None` if a custom build script is given.
+    ///
+    /// The language is given by the config, or found by the extension of the source file.
+    pub fn language(&self) -> Option<Language> {
+        if self.config.build_script.is_some() {
+            None
+        } else if let Some(ref language) = self.config.language {
+            Language::new(language)
+        } else {
+            Language::from_ext(self.config.source.extension()?)
+        }
+    }
+
     pub fn target_dir(&self) -> PathBuf {
         self.join("target")
     }
@@ -65,4 +81,5 @@ pub struct Config {
     pub language: Option<String>,
     pub build_script: Option<PathBuf>,
     pub timeout: Option<Duration>,
+    pub cgroups: Option<CgroupsConfig>,
 }
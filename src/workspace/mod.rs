@@ -2,6 +2,7 @@
 pub mod build;
 pub mod problem;
 pub mod runtime;
+pub mod store;
 
 use std::fs;
 use std::io;
@@ -13,6 +14,7 @@ pub use {
     build::BuildDir,
     problem::ProblemDir,
     runtime::{RunnerConfig, RuntimeDir, RuntimeHolder},
+    store::ProblemStore,
 };
 
 pub struct Workspace {
@@ -20,6 +22,7 @@ pub struct Workspace {
     build_dir: BuildDir,
     problem_dir: ProblemDir,
     runtime_dir: RuntimeDir,
+    checker_dir: RuntimeDir,
     config: Config,
 }
 
@@ -29,10 +32,14 @@ impl Workspace {
 
         let config_file = dir.join("config.toml");
         let toml_config = fs::read(config_file)?;
-        let config: Config = toml::from_slice(&toml_config)?;
+        let mut config: Config = toml::from_slice(&toml_config)?;
 
         let build_dir = BuildDir::from_path(dir.join("build"))?;
+        if let Some(language) = build_dir.language() {
+            config.runner = config.runner.merge(language.runner_defaults()?);
+        }
         let runtime_dir = RuntimeDir::from_path(dir.join("runtime"));
+        let checker_dir = RuntimeDir::from_path(dir.join("checker"));
         let problem_dir = ProblemDir::from_path(dir.join("problem"))?;
 
         let res = Workspace {
@@ -40,6 +47,7 @@ impl Workspace {
             build_dir,
             problem_dir,
             runtime_dir,
+            checker_dir,
             config,
         };
 
@@ -65,6 +73,11 @@ impl Workspace {
         &self.runtime_dir
     }
 
+    /// Runtime directory of the special judge or the interactor of the problem.
+    pub fn checker_dir(&self) -> &RuntimeDir {
+        &self.checker_dir
+    }
+
     pub fn problem_dir(&self) -> &ProblemDir {
         &self.problem_dir
     }
@@ -72,7 +85,13 @@ impl Workspace {
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Config {
+    /// Config of the runner, whose missing fields are given by the language of the source.
+    #[serde(default)]
     pub runner: RunnerConfig,
+    /// Judge all cases instead of stopping at the first failed case.
+    ///
+    /// Override the option in the configuration of the problem.
+    pub run_all_cases: Option<bool>,
 }
 
 #[cfg(test)]
@@ -1,6 +1,6 @@
 use std::fs;
 use std::io;
-use std::ops::Deref;
+use std::ops::{Deref, Range};
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
@@ -44,9 +44,9 @@ impl ProblemDir {
                 }
             }
             ProblemType::Interactive => {
-                if config.extern_program.is_some() {
+                if !inner.join("extern_program").exists() {
                     log::warn!(
-                        "The problem in {:?} is an interactive problem but the extern program is exist.",
+                        "The problem in {:?} is an interactive problem but the interactor is missing.",
                         inner,
                     );
                 }
@@ -64,6 +64,48 @@ impl ProblemDir {
                 }
             }
         }
+        if let Some(subtasks) = config.subtasks.as_ref() {
+            let cases = (0..)
+                .take_while(|index: &usize| inner.join(index.to_string()).exists())
+                .count();
+            for (index, subtask) in subtasks.iter().enumerate() {
+                if subtask.cases.start >= subtask.cases.end {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "The subtask {} of the problem in {:?} has no case",
+                            index, inner
+                        ),
+                    ));
+                }
+                if subtask.cases.end > cases {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "The subtask {} of the problem in {:?} contains the case {} but there are {} cases",
+                            index,
+                            inner,
+                            subtask.cases.end - 1,
+                            cases
+                        ),
+                    ));
+                }
+                if let Some(dependency) = subtask
+                    .dependencies
+                    .iter()
+                    .flatten()
+                    .find(|&&dependency| dependency >= index)
+                {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "The subtask {} of the problem in {:?} depends on a subsequent subtask {}",
+                            index, inner, dependency
+                        ),
+                    ));
+                }
+            }
+        }
         Ok(ProblemDir {
             inner: PathBuf::from(inner),
             config,
@@ -79,7 +121,19 @@ impl ProblemDir {
     }
 
     pub fn cases(&self) -> Cases {
-        Cases::new(&self)
+        Cases::new(self)
+    }
+
+    /// Get the case with the given index.
+    ///
+    /// Return `None` if the case does not exist.
+    pub fn case(&self, index: usize) -> Option<Case> {
+        let case = self.join(index.to_string());
+        if case.exists() {
+            Some(Case(case))
+        } else {
+            None
+        }
     }
 
     pub fn config(&self) -> &Config {
@@ -119,12 +173,9 @@ impl Iterator for Cases {
     type Item = Case;
 
     fn next(&mut self) -> Option<Case> {
-        let case = self.problem_dir.join(self.index.to_string());
-        if case.exists() {
-            self.index += 1;
-            return Some(Case(case));
-        }
-        None
+        let case = self.problem_dir.case(self.index)?;
+        self.index += 1;
+        Some(case)
     }
 }
 
@@ -159,6 +210,16 @@ pub struct Config {
     pub extern_program: Option<ExternProgram>,
     pub ignore_white_space_at_eol: Option<bool>,
     pub ignore_empty_line_at_eof: Option<bool>,
+    pub compare_mode: Option<CompareMode>,
+    /// Max absolute error of numbers in the `Float` compare mode.
+    pub absolute_error: Option<f64>,
+    /// Max relative error of numbers in the `Float` compare mode.
+    pub relative_error: Option<f64>,
+    /// Hide the first difference from the message of the wrong answer report.
+    pub hide_mismatch: Option<bool>,
+    pub subtasks: Option<Vec<Subtask>>,
+    /// Judge all cases instead of stopping at the first failed case.
+    pub run_all_cases: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -166,6 +227,10 @@ pub struct ResourceLimit {
     pub cpu_time: Duration,
     pub real_time: Duration,
     pub memory: usize,
+    /// Max size of the output file in bytes.
+    pub output: Option<usize>,
+    /// Max number of processes and threads of the program.
+    pub processes: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -175,9 +240,41 @@ pub enum ProblemType {
     Interactive,
 }
 
+/// The way to compare the output and the answer of a normal problem.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, Eq, PartialEq)]
+pub enum CompareMode {
+    /// Compare line by line.
+    #[default]
+    Line,
+    /// Compare token by token and ignore the layout of white spaces.
+    Token,
+    /// Compare token by token and accept numbers within the errors.
+    Float,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ExternProgram {
     pub source: PathBuf,
     pub language: Option<String>,
     pub build_script: Option<PathBuf>,
 }
+
+/// A group of cases which is scored as a whole.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Subtask {
+    /// Indexes of the cases in this subtask, the end is excluded.
+    pub cases: Range<usize>,
+    pub score: f64,
+    /// Indexes of the subtasks which must be passed before judging this subtask.
+    pub dependencies: Option<Vec<usize>>,
+    pub scoring: Option<Scoring>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, Eq, PartialEq)]
+pub enum Scoring {
+    /// Get the full score only if all cases are accepted.
+    #[default]
+    Min,
+    /// Get the score in proportion to the accepted cases.
+    Sum,
+}
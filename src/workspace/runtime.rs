@@ -3,10 +3,13 @@ use std::io;
 use std::collections::BTreeMap;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use nix;
 use serde::{Deserialize, Serialize};
 
+use crate::process::{self, cgroup};
+
 pub struct RuntimeDir {
     inner: PathBuf,
 }
@@ -31,6 +34,17 @@ impl RuntimeDir {
     pub fn output_file(&self) -> PathBuf {
         self.join("output")
     }
+
+    pub fn answer_file(&self) -> PathBuf {
+        self.join("answer")
+    }
+
+    /// Work directory of the overlay filesystem of the rootfs, next to the runtime dir.
+    pub fn work_dir(&self) -> PathBuf {
+        self.parent()
+            .expect("runtime dir should not be /")
+            .join("work")
+    }
 }
 
 impl From<&Path> for RuntimeDir {
@@ -76,12 +90,17 @@ impl RuntimeHolder {
 
         if let Some(config) = rootfs_config {
             with_rootfs = true;
-            work_dir = Some(
-                runtime_dir
-                    .parent()
-                    .expect("runtime dir should not be /")
-                    .join("work"),
-            );
+            work_dir = Some(RuntimeDir::from(runtime_dir.as_path()).work_dir());
+            if process::rootless() {
+                // the overlay is mounted in the namespaces of the program without root
+                fs::create_dir_all(work_dir.as_ref().unwrap())?;
+                return Ok(RuntimeHolder {
+                    runtime_dir,
+                    work_dir,
+                    upper_dir,
+                    with_rootfs,
+                });
+            }
             upper_dir = Some(
                 runtime_dir
                     .parent()
@@ -89,8 +108,8 @@ impl RuntimeHolder {
                     .join("upper"),
             );
 
-            fs::create_dir_all(&work_dir.as_ref().unwrap())?;
-            fs::create_dir_all(&upper_dir.as_ref().unwrap())?;
+            fs::create_dir_all(work_dir.as_ref().unwrap())?;
+            fs::create_dir_all(upper_dir.as_ref().unwrap())?;
 
             let data = format!(
                 "lowerdir={},upperdir={},workdir={}",
@@ -106,14 +125,14 @@ impl RuntimeHolder {
                 nix::mount::MsFlags::empty(),
                 Some(data.as_str()),
             )
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "mount runtime overlay failed!"))?;
+            .map_err(|_| io::Error::other("mount runtime overlay failed!"))?;
         }
 
         Ok(RuntimeHolder {
-            runtime_dir: runtime_dir,
-            work_dir: work_dir,
-            upper_dir: upper_dir,
-            with_rootfs: with_rootfs,
+            runtime_dir,
+            work_dir,
+            upper_dir,
+            with_rootfs,
         })
     }
 }
@@ -122,15 +141,17 @@ impl Drop for RuntimeHolder {
     fn drop(&mut self) {
         log::debug!("droping runtime dir");
         if self.with_rootfs {
-            if let Err(e) = nix::mount::umount(&self.runtime_dir) {
-                log::debug!(
-                    "Error when umount runntime_dir {}, err: {}",
-                    self.runtime_dir.display(),
-                    e
-                );
+            // the overlay is only mounted here with an upper dir
+            if self.upper_dir.is_some() {
+                if let Err(e) = nix::mount::umount(&self.runtime_dir) {
+                    log::debug!(
+                        "Error when umount runntime_dir {}, err: {}",
+                        self.runtime_dir.display(),
+                        e
+                    );
+                }
             }
             let work_dir = self.work_dir.as_ref().unwrap();
-            let upper_dir = self.upper_dir.as_ref().unwrap();
 
             if let Err(e) = fs::remove_dir_all(work_dir) {
                 log::debug!(
@@ -139,12 +160,14 @@ impl Drop for RuntimeHolder {
                     e
                 );
             }
-            if let Err(e) = fs::remove_dir_all(upper_dir) {
-                log::debug!(
-                    "Error when remove upper dir {}, err: {}",
-                    upper_dir.display(),
-                    e
-                );
+            if let Some(upper_dir) = self.upper_dir.as_ref() {
+                if let Err(e) = fs::remove_dir_all(upper_dir) {
+                    log::debug!(
+                        "Error when remove upper dir {}, err: {}",
+                        upper_dir.display(),
+                        e
+                    );
+                }
             }
         }
 
@@ -158,19 +181,58 @@ impl Drop for RuntimeHolder {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
 pub struct RunnerConfig {
     pub command: Option<PathBuf>,
     pub args: Option<Vec<String>>,
     pub cgroups: Option<CgroupsConfig>,
     pub seccomp: Option<SeccompConfig>,
+    /// Capabilities kept for the program. All capabilities are dropped by default.
+    pub capabilities: Option<Vec<String>>,
+    /// Set `no_new_privs` for the program, which is `true` by default.
+    pub no_new_privs: Option<bool>,
+    /// Namespaces unshared for the program.
+    ///
+    /// All namespaces except `CGROUP` and `USER` are unshared by default.
     pub namespaces: Option<Vec<Namespace>>,
+    /// The uid which root in the user namespace is mapped to, which is `nobody` by default.
+    pub uid: Option<u32>,
+    /// The gid which root in the user namespace is mapped to, which is `nogroup` by default.
+    pub gid: Option<u32>,
     pub rootfs: Option<RootfsConfig>,
+    /// Directories of the host bound read-only to the same paths in the root of the program,
+    /// which provide the toolchain used by the command. The `MOUNT` namespace is required.
+    ///
+    /// The proc filesystem of the host can not be mounted, and a new one is mounted instead
+    /// if the `PID` namespace is unshared.
+    pub mounts: Option<Vec<PathBuf>>,
     pub envs: Option<BTreeMap<String, String>>,
     pub time_limit_ratio: Option<f64>,
     pub mem_limit_ratio: Option<f64>,
 }
 
+impl RunnerConfig {
+    /// Fill the fields missing in this config with the ones of `defaults`.
+    pub fn merge(self, defaults: RunnerConfig) -> RunnerConfig {
+        RunnerConfig {
+            command: self.command.or(defaults.command),
+            args: self.args.or(defaults.args),
+            cgroups: self.cgroups.or(defaults.cgroups),
+            seccomp: self.seccomp.or(defaults.seccomp),
+            capabilities: self.capabilities.or(defaults.capabilities),
+            no_new_privs: self.no_new_privs.or(defaults.no_new_privs),
+            namespaces: self.namespaces.or(defaults.namespaces),
+            uid: self.uid.or(defaults.uid),
+            gid: self.gid.or(defaults.gid),
+            rootfs: self.rootfs.or(defaults.rootfs),
+            mounts: self.mounts.or(defaults.mounts),
+            envs: self.envs.or(defaults.envs),
+            time_limit_ratio: self.time_limit_ratio.or(defaults.time_limit_ratio),
+            mem_limit_ratio: self.mem_limit_ratio.or(defaults.mem_limit_ratio),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct RootfsConfig {
     pub base_path: PathBuf,
@@ -188,8 +250,153 @@ pub enum Namespace {
     UTS,
 }
 
+impl Namespace {
+    /// Namespaces unshared when none is configured.
+    pub fn defaults() -> Vec<Namespace> {
+        vec![
+            Namespace::IPC,
+            Namespace::NETWORK,
+            Namespace::MOUNT,
+            Namespace::PID,
+            Namespace::UTS,
+        ]
+    }
+
+    pub fn clone_flag(&self) -> nix::sched::CloneFlags {
+        use nix::sched::CloneFlags;
+        match self {
+            Namespace::CGROUP => CloneFlags::CLONE_NEWCGROUP,
+            Namespace::IPC => CloneFlags::CLONE_NEWIPC,
+            Namespace::NETWORK => CloneFlags::CLONE_NEWNET,
+            Namespace::MOUNT => CloneFlags::CLONE_NEWNS,
+            Namespace::PID => CloneFlags::CLONE_NEWPID,
+            Namespace::USER => CloneFlags::CLONE_NEWUSER,
+            Namespace::UTS => CloneFlags::CLONE_NEWUTS,
+        }
+    }
+}
+
+/// Cgroup of the program.
+///
+/// All controllers are enabled with a single cpu by default.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct CgroupsConfig {
+    pub controllers: Option<Vec<CgroupController>>,
+    /// Number of cpus allocated by the cpuset controller.
+    pub cpus: Option<u32>,
+    /// Max memory usage in bytes.
+    ///
+    /// The memory limit of the problem is used instead for the program if it is smaller.
+    pub memory_limit: Option<usize>,
+    /// Max memory and swap usage in bytes.
+    ///
+    /// It is the memory limit of the program by default, so the program can not use swap.
+    pub memsw_limit: Option<usize>,
+    /// Max number of processes and threads, which requires the `Pids` controller.
+    pub pids_limit: Option<usize>,
+    pub cpu_period: Option<Duration>,
+    /// Max cpu time in each `cpu_period`, which is 100ms by default.
+    pub cpu_quota: Option<Duration>,
+}
+
+impl CgroupsConfig {
+    /// Generate the cgroup builder by the configuration.
+    pub fn builder(&self) -> cgroup::Builder {
+        let controllers = match &self.controllers {
+            Some(controllers) => controllers.clone(),
+            None => vec![
+                CgroupController::Cpu,
+                CgroupController::CpuAcct,
+                CgroupController::Cpuset,
+                CgroupController::Memory,
+            ],
+        };
+        let cpus = self.cpus.unwrap_or(1);
+        let mut builder = cgroup::Builder::new()
+            .cpu_controller(controllers.contains(&CgroupController::Cpu))
+            .cpuacct_controller(controllers.contains(&CgroupController::CpuAcct))
+            .memory_controller(controllers.contains(&CgroupController::Memory))
+            .cpuset_controller(
+                controllers.contains(&CgroupController::Cpuset) && cpus > 0,
+                cpus,
+            )
+            .pids_controller(controllers.contains(&CgroupController::Pids));
+        if let Some(limit) = self.pids_limit {
+            builder = builder.pids_limit(limit);
+        }
+        if let Some(limit) = self.memory_limit {
+            builder = builder.memory_limit(limit);
+        }
+        if let Some(limit) = self.memsw_limit {
+            builder = builder.memsw_limit(limit);
+        }
+        if let Some(quota) = self.cpu_quota {
+            let period = self.cpu_period.unwrap_or(DEFAULT_CPU_PERIOD);
+            builder = builder.cpu_quota(period, quota);
+        }
+        builder
+    }
+}
+
+/// Default period of the cpu quota.
+const DEFAULT_CPU_PERIOD: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CgroupController {
+    Cpu,
+    CpuAcct,
+    Cpuset,
+    Memory,
+    Pids,
+}
+
+/// System calls filter loaded before executing the program.
+///
+/// The rules of the profile are added before the custom rules.
+/// System calls matching no rules take the default action,
+/// which is `Kill` by default.
 #[derive(Serialize, Deserialize, Clone, Default, Debug)]
-pub struct CgroupsConfig {}
+pub struct SeccompConfig {
+    pub profile: Option<SeccompProfile>,
+    pub default_action: Option<SeccompAction>,
+    pub rules: Option<Vec<SeccompRule>>,
+}
+
+/// Built-in whitelists of system calls.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SeccompProfile {
+    /// System calls used by common C/C++ programs.
+    Default,
+    /// System calls used by JVM and other multi-threaded runtimes.
+    Jvm,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SeccompAction {
+    Allow,
+    Kill,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct SeccompConfig {}
+pub struct SeccompRule {
+    pub syscall: String,
+    pub action: SeccompAction,
+    /// Conditions of the arguments of the system call in order.
+    pub args: Option<Vec<SeccompArg>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SeccompArg {
+    pub op: SeccompCmpOp,
+    pub value: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SeccompCmpOp {
+    NE,
+    LT,
+    LE,
+    EQ,
+    GE,
+    GT,
+}
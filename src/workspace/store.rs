@@ -0,0 +1,106 @@
+//! Problems cached on the server, which are used by the tasks referencing them.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tempfile::TempDir;
+
+use super::ProblemDir;
+
+/// Version of the problems cached without a version.
+const DEFAULT_VERSION: &str = "latest";
+
+/// Directory of the problems being cached or replaced.
+const STAGING_DIR: &str = ".staging";
+
+/// Problems stored in `{root}/{id}/{version}`.
+#[derive(Debug, Clone)]
+pub struct ProblemStore {
+    root: PathBuf,
+}
+
+impl ProblemStore {
+    pub fn new<P: AsRef<Path>>(root: P) -> io::Result<ProblemStore> {
+        let root = root.as_ref();
+        fs::create_dir_all(root.join(STAGING_DIR))?;
+        Ok(ProblemStore {
+            root: root.to_owned(),
+        })
+    }
+
+    /// Get the cached problem with the id and the version.
+    pub fn get(&self, id: &str, version: Option<&str>) -> io::Result<ProblemDir> {
+        let path = self.problem_path(id, version)?;
+        if !path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("The problem {} is not cached", id),
+            ));
+        }
+        ProblemDir::from_path(path)
+    }
+
+    /// Create a directory for preparing a problem, which is cached by `insert`.
+    ///
+    /// The problem should be placed in the `problem` directory of it.
+    pub fn staging_dir(&self) -> io::Result<TempDir> {
+        tempfile::tempdir_in(self.root.join(STAGING_DIR))
+    }
+
+    /// Cache the problem prepared in the staging directory.
+    ///
+    /// The problem with the same id and version is replaced. The files linked by
+    /// the running tasks are kept until the tasks are finished.
+    pub fn insert(
+        &self,
+        id: &str,
+        version: Option<&str>,
+        staging_dir: TempDir,
+    ) -> io::Result<ProblemDir> {
+        let path = self.problem_path(id, version)?;
+        ProblemDir::from_path(staging_dir.path().join("problem"))?;
+        fs::create_dir_all(path.parent().unwrap())?;
+        if path.exists() {
+            // the replaced problem is removed with the staging directory
+            fs::rename(&path, staging_dir.path().join("replaced"))?;
+        }
+        fs::rename(staging_dir.path().join("problem"), &path)?;
+        ProblemDir::from_path(path)
+    }
+
+    /// Create the problem directory of a workspace from the cached problem.
+    ///
+    /// The files are hard linked into `dst` if possible, except the extern program
+    /// which is copied since it is built in place.
+    pub fn link(&self, problem: &ProblemDir, dst: &Path) -> io::Result<()> {
+        link_dir(problem, dst, &problem.extern_program())
+    }
+
+    fn problem_path(&self, id: &str, version: Option<&str>) -> io::Result<PathBuf> {
+        let version = version.unwrap_or(DEFAULT_VERSION);
+        for name in [id, version].iter() {
+            if name.is_empty() || name.starts_with('.') || name.contains('/') {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{:?} is not a valid id or version of problem", name),
+                ));
+            }
+        }
+        Ok(self.root.join(id).join(version))
+    }
+}
+
+/// Link the files in `src` into `dst` recursively, copying the ones in `copied`.
+fn link_dir(src: &Path, dst: &Path, copied: &Path) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in src.read_dir()? {
+        let entry = entry?;
+        let (src, dst) = (entry.path(), dst.join(entry.file_name()));
+        if entry.file_type()?.is_dir() {
+            link_dir(&src, &dst, copied)?;
+        } else if src.starts_with(copied) || fs::hard_link(&src, &dst).is_err() {
+            fs::copy(&src, &dst)?;
+        }
+    }
+    Ok(())
+}
@@ -1,6 +1,11 @@
-use super::Workspace;
+use super::problem::ProblemDir;
+use super::runtime::{RunnerConfig, SeccompAction, SeccompCmpOp, SeccompProfile};
+use super::{ProblemStore, Workspace};
 
+use std::fs;
 use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
 
 #[test]
 fn test_normal_c() -> io::Result<()> {
@@ -19,3 +24,153 @@ fn test_custom_script() -> io::Result<()> {
     let _workspace = Workspace::from_path("examples/workspace/custom_script/")?;
     Ok(())
 }
+
+#[test]
+fn test_float_c() -> io::Result<()> {
+    let _workspace = Workspace::from_path("examples/workspace/float_c/")?;
+    Ok(())
+}
+
+#[test]
+fn test_subtask_c() -> io::Result<()> {
+    let workspace = Workspace::from_path("examples/workspace/subtask_c/")?;
+    let subtasks = workspace.problem_dir().config().subtasks.as_ref().unwrap();
+    assert_eq!(subtasks.len(), 2);
+    assert_eq!(subtasks[1].cases, 0..3);
+    assert_eq!(subtasks[1].dependencies, Some(vec![0]));
+
+    // the subtask containing a missing case is rejected when the problem is loaded
+    let problem = tempfile::tempdir()?;
+    let config = fs::read_to_string("examples/workspace/subtask_c/problem/config.toml")?;
+    fs::write(
+        problem.path().join("config.toml"),
+        config.replace("end = 3", "end = 4"),
+    )?;
+    for index in 0..3 {
+        fs::create_dir(problem.path().join(index.to_string()))?;
+    }
+    let err = ProblemDir::from_path(problem.path()).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    Ok(())
+}
+
+#[test]
+fn test_language_runner() -> io::Result<()> {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir_all(dir.path().join("build"))?;
+    fs::create_dir_all(dir.path().join("problem"))?;
+    fs::copy(
+        "examples/workspace/normal_c/problem/config.toml",
+        dir.path().join("problem/config.toml"),
+    )?;
+    fs::write(
+        dir.path().join("build/config.toml"),
+        "source = \"Main.java\"\n",
+    )?;
+
+    // the runner config of the language is used without a runner section
+    fs::write(dir.path().join("config.toml"), "")?;
+    let workspace = Workspace::from_path(dir.path())?;
+    let runner = &workspace.config().runner;
+    assert_eq!(runner.command.as_deref(), Some(Path::new("java")));
+    assert_eq!(runner.args, Some(vec![String::from("Main")]));
+    assert_eq!(runner.time_limit_ratio, Some(2.0));
+
+    // the fields of the workspace override the ones of the language
+    fs::write(
+        dir.path().join("config.toml"),
+        "[runner]\ncommand = \"/usr/bin/java\"\ntime_limit_ratio = 3.0\n",
+    )?;
+    let workspace = Workspace::from_path(dir.path())?;
+    let runner = &workspace.config().runner;
+    assert_eq!(runner.command.as_deref(), Some(Path::new("/usr/bin/java")));
+    assert_eq!(runner.args, Some(vec![String::from("Main")]));
+    assert_eq!(runner.time_limit_ratio, Some(3.0));
+    assert_eq!(runner.mem_limit_ratio, Some(2.0));
+    Ok(())
+}
+
+#[test]
+fn test_seccomp_config() {
+    let config: RunnerConfig = toml::from_str(
+        r#"
+        [seccomp]
+        profile = "Jvm"
+        rules = [
+            { syscall = "fork", action = "Kill" },
+            { syscall = "write", action = "Allow", args = [{ op = "LE", value = 2 }] },
+        ]
+        "#,
+    )
+    .unwrap();
+    let seccomp = config.seccomp.unwrap();
+    assert_eq!(seccomp.profile, Some(SeccompProfile::Jvm));
+    assert_eq!(seccomp.default_action, None);
+    let rules = seccomp.rules.unwrap();
+    assert_eq!(rules[0].action, SeccompAction::Kill);
+    assert_eq!(rules[1].args.as_ref().unwrap()[0].op, SeccompCmpOp::LE);
+}
+
+#[test]
+fn test_problem_store() -> io::Result<()> {
+    let root = tempfile::tempdir()?;
+    let store = ProblemStore::new(root.path())?;
+    for (id, version) in [
+        ("..", None),
+        ("a/b", None),
+        ("a", Some("")),
+        ("a", Some(".b")),
+    ]
+    .iter()
+    {
+        let err = store.get(id, *version).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+    assert_eq!(
+        store.get("spj", None).err().unwrap().kind(),
+        io::ErrorKind::NotFound
+    );
+
+    let staging_dir = store.staging_dir()?;
+    copy_dir(
+        "examples/workspace/spj_c/problem",
+        &staging_dir.path().join("problem"),
+    )?;
+    let problem = store.insert("spj", None, staging_dir)?;
+    assert_eq!(problem.as_path(), store.get("spj", None)?.as_path());
+
+    let dst = tempfile::tempdir()?;
+    store.link(&problem, &dst.path().join("problem"))?;
+    let case = dst.path().join("problem/0/input");
+    let extern_program = dst.path().join("problem/extern_program/spj.c");
+    assert_eq!(fs::read(&case)?, fs::read(problem.join("0/input"))?);
+    assert_eq!(fs::metadata(&case)?.nlink(), 2);
+    assert_eq!(fs::metadata(&extern_program)?.nlink(), 1);
+
+    // the linked files are kept after the problem is replaced
+    let staging_dir = store.staging_dir()?;
+    copy_dir(
+        "examples/workspace/normal_c/problem",
+        &staging_dir.path().join("problem"),
+    )?;
+    store.insert("spj", None, staging_dir)?;
+    assert!(!problem.join("extern_program").exists());
+    assert!(case.exists());
+    assert_eq!(fs::read_dir(root.path().join(".staging"))?.count(), 0);
+
+    Ok(())
+}
+
+fn copy_dir(src: &str, dst: &Path) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let dst = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(entry.path().to_str().unwrap(), &dst)?;
+        } else {
+            fs::copy(entry.path(), dst)?;
+        }
+    }
+    Ok(())
+}